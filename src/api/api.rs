@@ -1,52 +1,175 @@
+use super::assets::static_files;
 use super::docs::ApiDoc;
 use super::message::Empty;
 use crate::api::message::{
-    Activity, Donate, DoubleCoord, GameInfo, Leaderboard, SingleCoord, WoopMap,
+    Activity, Batch, BatchResult, Donate, DoubleCoord, GameHealth, GameInfo, GameList, GameStats,
+    GameSummary, GraphFormat, GraphQuery, GroupName, InteractionGraph, Invite, Leaderboard, Me,
+    MessageList, NewGroup, OrderList, Proposal, Readiness, SendMessage, SingleCoord, Step,
+    StreamQuery, TeamLeaderboard, TeamList, TeamName, TeamScore, TreatyList, WoopMap,
 };
-use crate::game::game::Game;
+use crate::game::action::Action;
+use crate::game::error::WoopError;
+use crate::game::game::{Game, Phase, MAX_BATCH_SIZE};
+use crate::game::log::EventQuery;
+use crate::game::messages::Channel;
+use crate::game::orders::Order;
 use crate::game::player::Player;
-use std::collections::HashMap;
+use crate::game::replay::board_at;
+use crate::game::replies::fingerprint;
+use crate::game::tactics::{zord_tactics, ActionCosts};
+use crate::game::vision::Sight;
+use crate::metrics::METRICS;
+use crate::registry::{GameRegistry, SharedGame};
+use crate::scheduler::TICK_INTERVAL;
+use serde::Serialize;
 use std::convert::Infallible;
-use std::fs;
-use std::sync::{Arc, Mutex};
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::time::UNIX_EPOCH;
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
 use utoipa::OpenApi;
 use utoipa_rapidoc::RapiDoc;
 use warp::http::StatusCode;
-use warp::reject::Rejection;
+use warp::reject::{Reject, Rejection};
 use warp::reply::Json;
+use warp::sse::Event;
 use warp::Filter;
 use warp::Reply as WarpReply;
 
-async fn handle_rejection(_: Rejection) -> Result<impl WarpReply, Infallible> {
+impl Reject for WoopError {}
+
+async fn handle_rejection(rejection: Rejection) -> Result<impl WarpReply, Infallible> {
+    if let Some(err) = rejection.find::<WoopError>() {
+        let status = match err {
+            WoopError::ShuttingDown => StatusCode::SERVICE_UNAVAILABLE,
+            _ => StatusCode::NOT_FOUND,
+        };
+        let json = warp::reply::json(&Empty::Error(err.to_string()));
+        return Ok(warp::reply::with_status(json, status));
+    }
     let msg = "Incorrect interaction with the api. Check method, endpoint and JSON data";
     let json = warp::reply::json(&Empty::Error(msg.to_string()));
     Ok(warp::reply::with_status(json, StatusCode::BAD_REQUEST))
 }
 
 fn get_game_status(game: &Game) -> Result<Json, ()> {
-    match game.day {
-        0 => Ok(warp::reply::json(&Empty::Error(
+    match game.phase() {
+        Phase::NotStarted => Ok(warp::reply::json(&Empty::Error(
             "Game hasn't started yet".to_string(),
         ))),
-        29 => Ok(warp::reply::json(&Empty::Error(
+        Phase::Ended => Ok(warp::reply::json(&Empty::Error(
             "Game has ended".to_string(),
         ))),
-        _ => Err(()),
+        Phase::Running => Err(()),
+    }
+}
+
+// The reply to send again when the idempotency key was already used today, or the error when it
+// can't be used
+fn replayed(game: &Game, player: &str, key: Option<&str>, request: &str) -> Option<Json> {
+    match game.replies.get(player, key, request) {
+        Ok(reply) => reply.map(warp::reply::json),
+        Err(err) => Some(error_reply(game, err)),
+    }
+}
+
+// Replies to an action, remembering the reply when the request came with an idempotency key
+fn action_reply(
+    game: &mut Game,
+    player: &str,
+    key: Option<String>,
+    request: String,
+    result: Result<impl Serialize, WoopError>,
+) -> Json {
+    let reply = match result {
+        Ok(reply) => serde_json::to_value(reply).unwrap(),
+        Err(err) => {
+            METRICS.observe_error(game.id.as_str(), &err);
+            serde_json::to_value(Empty::Error(err.to_string())).unwrap()
+        }
+    };
+    let json = warp::reply::json(&reply);
+    if let Some(key) = key {
+        game.replies.insert(player, key, request, reply);
     }
+    json
 }
 
-pub async fn start_api(game: Arc<Mutex<Game>>) {
-    let shoot_action = warp::path("shoot")
+// Who is looking at the board, nobody unless the request carries credentials
+fn viewer(
+    game: &Game,
+    username: Option<String>,
+    pass: Option<String>,
+) -> Result<Option<String>, WoopError> {
+    match (username, pass) {
+        (Some(username), Some(pass)) => game
+            .authenticate(username.as_str(), pass.as_str())
+            .map(|_| Some(username)),
+        _ => Ok(None),
+    }
+}
+
+fn error_reply(game: &Game, err: WoopError) -> Json {
+    METRICS.observe_error(game.id.as_str(), &err);
+    warp::reply::json(&Empty::Error(err.to_string()))
+}
+
+/// Serves the api until `shutdown` resolves. Once it does actions are refused, while the requests
+/// that are already being handled are allowed to complete.
+pub async fn start_api(
+    registry: Arc<GameRegistry>,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+) {
+    let closing = Arc::new(AtomicBool::new(false));
+    // Ends the live streams on shutdown, they would keep the server waiting otherwise
+    let (stop_streams, _) = broadcast::channel::<()>(1);
+
+    let with_game = warp::path("games")
+        .and(warp::path::param::<String>())
+        .and_then({
+            let registry = registry.clone();
+            move |id: String| {
+                let game = registry.get(id.as_str()).map_err(warp::reject::custom);
+                async move { game }
+            }
+        });
+
+    let accepting_actions = warp::any()
+        .and_then({
+            let closing = closing.clone();
+            move || {
+                let open = match closing.load(Ordering::SeqCst) {
+                    true => Err(warp::reject::custom(WoopError::ShuttingDown)),
+                    false => Ok(()),
+                };
+                async move { open }
+            }
+        })
+        .untuple_one();
+    let with_action_game = accepting_actions.and(with_game.clone());
+
+    let shoot_action = with_action_game
+        .clone()
+        .and(warp::path("shoot"))
         .and(warp::body::json())
         .and(warp::header("username"))
         .and(warp::header("token"))
-        .map({
-            let game = game.clone();
-            move |req: DoubleCoord, username: String, pass: String| {
-                let mut game = game.lock().unwrap();
+        .and(warp::header::optional("idempotency-key"))
+        .then(
+            |game: SharedGame,
+             req: DoubleCoord,
+             username: String,
+             pass: String,
+             key: Option<String>| async move {
+                let mut game = game.write().await;
                 if let Err(err) = game.authenticate(username.as_str(), pass.as_str()) {
-                    return warp::reply::json(&Empty::Error(err.to_string()));
+                    return error_reply(&game, err);
+                }
+                let request = fingerprint("shoot", &req);
+                if let Some(reply) = replayed(&game, username.as_str(), key.as_deref(), &request) {
+                    return reply;
                 }
 
                 if let Ok(resp) = get_game_status(&game) {
@@ -54,23 +177,37 @@ pub async fn start_api(game: Arc<Mutex<Game>>) {
                 }
                 let (x_f, y_f) = req.from;
                 let (x_t, y_t) = req.to;
-                match game.player_shoot(username.as_str(), x_f, y_f, x_t, y_t) {
-                    Ok(_) => warp::reply::json(&Empty::Ok),
-                    Err(err) => warp::reply::json(&Empty::Error(err.to_string())),
-                }
-            }
-        });
+                let result = game.player_shoot(username.as_str(), x_f, y_f, x_t, y_t);
+                action_reply(
+                    &mut game,
+                    username.as_str(),
+                    key,
+                    request,
+                    result.map(|_| Empty::Ok),
+                )
+            },
+        );
 
-    let move_action = warp::path("move")
+    let move_action = with_action_game
+        .clone()
+        .and(warp::path("move"))
         .and(warp::body::json())
         .and(warp::header("username"))
         .and(warp::header("token"))
-        .map({
-            let game = game.clone();
-            move |req: DoubleCoord, username: String, pass: String| {
-                let mut game = game.lock().unwrap();
+        .and(warp::header::optional("idempotency-key"))
+        .then(
+            |game: SharedGame,
+             req: DoubleCoord,
+             username: String,
+             pass: String,
+             key: Option<String>| async move {
+                let mut game = game.write().await;
                 if let Err(err) = game.authenticate(username.as_str(), pass.as_str()) {
-                    return warp::reply::json(&Empty::Error(err.to_string()));
+                    return error_reply(&game, err);
+                }
+                let request = fingerprint("move", &req);
+                if let Some(reply) = replayed(&game, username.as_str(), key.as_deref(), &request) {
+                    return reply;
                 }
 
                 if let Ok(resp) = get_game_status(&game) {
@@ -78,130 +215,436 @@ pub async fn start_api(game: Arc<Mutex<Game>>) {
                 }
                 let (x_f, y_f) = req.from;
                 let (x_t, y_t) = req.to;
-                match game.move_zord(username.as_str(), x_f, y_f, x_t, y_t) {
-                    Ok(_) => warp::reply::json(&Empty::Ok),
-                    Err(err) => warp::reply::json(&Empty::Error(err.to_string())),
-                }
-            }
-        });
+                let result = game.move_zord(username.as_str(), x_f, y_f, x_t, y_t);
+                action_reply(
+                    &mut game,
+                    username.as_str(),
+                    key,
+                    request,
+                    result.map(|_| Empty::Ok),
+                )
+            },
+        );
 
-    let shield_action = warp::path("shield")
+    let shield_action = with_action_game
+        .clone()
+        .and(warp::path("shield"))
         .and(warp::body::json())
         .and(warp::header("username"))
         .and(warp::header("token"))
-        .map({
-            let game = game.clone();
-            move |req: SingleCoord, username: String, pass: String| {
-                let mut game = game.lock().unwrap();
+        .and(warp::header::optional("idempotency-key"))
+        .then(
+            |game: SharedGame,
+             req: SingleCoord,
+             username: String,
+             pass: String,
+             key: Option<String>| async move {
+                let mut game = game.write().await;
                 if let Err(err) = game.authenticate(username.as_str(), pass.as_str()) {
-                    return warp::reply::json(&Empty::Error(err.to_string()));
+                    return error_reply(&game, err);
+                }
+                let request = fingerprint("shield", &req);
+                if let Some(reply) = replayed(&game, username.as_str(), key.as_deref(), &request) {
+                    return reply;
                 }
 
                 if let Ok(resp) = get_game_status(&game) {
                     return resp;
                 }
                 let (x, y) = req.coord;
-                match game.generate_shield(username.as_str(), x, y) {
-                    Ok(()) => warp::reply::json(&Empty::Ok),
-                    Err(err) => warp::reply::json(&Empty::Error(err.to_string())),
-                }
-            }
-        });
+                let result = game.generate_shield(username.as_str(), x, y);
+                action_reply(
+                    &mut game,
+                    username.as_str(),
+                    key,
+                    request,
+                    result.map(|_| Empty::Ok),
+                )
+            },
+        );
 
-    let increase_action = warp::path("increase-range")
+    let increase_action = with_action_game
+        .clone()
+        .and(warp::path("increase-range"))
         .and(warp::body::json())
         .and(warp::header("username"))
         .and(warp::header("token"))
-        .map({
-            let game = game.clone();
-            move |req: SingleCoord, username: String, pass: String| {
-                let mut game = game.lock().unwrap();
+        .and(warp::header::optional("idempotency-key"))
+        .then(
+            |game: SharedGame,
+             req: SingleCoord,
+             username: String,
+             pass: String,
+             key: Option<String>| async move {
+                let mut game = game.write().await;
                 if let Err(err) = game.authenticate(username.as_str(), pass.as_str()) {
-                    return warp::reply::json(&Empty::Error(err.to_string()));
+                    return error_reply(&game, err);
+                }
+                let request = fingerprint("increase-range", &req);
+                if let Some(reply) = replayed(&game, username.as_str(), key.as_deref(), &request) {
+                    return reply;
                 }
 
                 if let Ok(resp) = get_game_status(&game) {
                     return resp;
                 }
                 let (x, y) = req.coord;
-                match game.increase_range(username.as_str(), x, y) {
-                    Ok(()) => warp::reply::json(&Empty::Ok),
-                    Err(err) => warp::reply::json(&Empty::Error(err.to_string())),
+                let result = game.increase_range(username.as_str(), x, y);
+                action_reply(
+                    &mut game,
+                    username.as_str(),
+                    key,
+                    request,
+                    result.map(|_| Empty::Ok),
+                )
+            },
+        );
+
+    let donate_action =
+        with_action_game
+            .clone()
+            .and(warp::path("donate-points"))
+            .and(warp::body::json())
+            .and(warp::header("username"))
+            .and(warp::header("token"))
+            .and(warp::header::optional("idempotency-key"))
+            .then(
+                |game: SharedGame,
+                 req: Donate,
+                 username: String,
+                 pass: String,
+                 key: Option<String>| async move {
+                    let mut game = game.write().await;
+                    if let Err(err) = game.authenticate(username.as_str(), pass.as_str()) {
+                        return error_reply(&game, err);
+                    }
+                    let request = fingerprint("donate-points", &req);
+                    if let Some(reply) =
+                        replayed(&game, username.as_str(), key.as_deref(), &request)
+                    {
+                        return reply;
+                    }
+
+                    if let Ok(resp) = get_game_status(&game) {
+                        return resp;
+                    }
+                    let result =
+                        game.donate_points(username.as_str(), req.receiver.as_str(), req.amount);
+                    action_reply(
+                        &mut game,
+                        username.as_str(),
+                        key,
+                        request,
+                        result.map(|_| Empty::Ok),
+                    )
+                },
+            );
+
+    let build_action = with_action_game
+        .clone()
+        .and(warp::path("build-zord"))
+        .and(warp::body::json())
+        .and(warp::header("username"))
+        .and(warp::header("token"))
+        .and(warp::header::optional("idempotency-key"))
+        .then(
+            |game: SharedGame,
+             req: SingleCoord,
+             username: String,
+             pass: String,
+             key: Option<String>| async move {
+                let mut game = game.write().await;
+                if let Err(err) = game.authenticate(username.as_str(), pass.as_str()) {
+                    return error_reply(&game, err);
+                }
+                let request = fingerprint("build-zord", &req);
+                if let Some(reply) = replayed(&game, username.as_str(), key.as_deref(), &request) {
+                    return reply;
                 }
-            }
-        });
 
-    let donate_action = warp::path("donate-points")
+                if let Ok(resp) = get_game_status(&game) {
+                    return resp;
+                }
+                let (x, y) = req.coord;
+                let result = game.build_zord(username.as_str(), x, y);
+                action_reply(
+                    &mut game,
+                    username.as_str(),
+                    key,
+                    request,
+                    result.map(|_| Empty::Ok),
+                )
+            },
+        );
+
+    let batch_action =
+        with_action_game
+            .clone()
+            .and(warp::path("batch"))
+            .and(warp::body::json())
+            .and(warp::header("username"))
+            .and(warp::header("token"))
+            .and(warp::header::optional("idempotency-key"))
+            .then(
+                |game: SharedGame,
+                 req: Batch,
+                 username: String,
+                 pass: String,
+                 key: Option<String>| async move {
+                    let mut game = game.write().await;
+                    if let Err(err) = game.authenticate(username.as_str(), pass.as_str()) {
+                        return error_reply(&game, err);
+                    }
+                    let request = fingerprint("batch", &req);
+                    if let Some(reply) =
+                        replayed(&game, username.as_str(), key.as_deref(), &request)
+                    {
+                        return reply;
+                    }
+
+                    if let Ok(resp) = get_game_status(&game) {
+                        return resp;
+                    }
+                    if req.actions.len() > MAX_BATCH_SIZE {
+                        return error_reply(&game, WoopError::BatchTooLong);
+                    }
+                    let results =
+                        game.apply_batch(username.as_str(), &req.actions, req.best_effort);
+                    let failed = results.iter().any(|result| result.is_err());
+                    let mut steps: Vec<Step> = results
+                        .into_iter()
+                        .map(|result| match result {
+                            Ok(()) => Step::Ok,
+                            Err(err) => {
+                                METRICS.observe_error(game.id.as_str(), &err);
+                                Step::Error(err.to_string())
+                            }
+                        })
+                        .collect();
+                    let applied = match failed && !req.best_effort {
+                        true => 0,
+                        false => steps.iter().filter(|step| matches!(step, Step::Ok)).count(),
+                    };
+                    steps.resize_with(req.actions.len(), || Step::Skipped);
+                    let result: Result<_, WoopError> = Ok(BatchResult { applied, steps });
+                    action_reply(&mut game, username.as_str(), key, request, result)
+                },
+            );
+
+    let preview_action = with_game
+        .clone()
+        .and(warp::path("preview"))
         .and(warp::body::json())
         .and(warp::header("username"))
         .and(warp::header("token"))
-        .map({
-            let game = game.clone();
-            move |req: Donate, username: String, pass: String| {
-                let mut game = game.lock().unwrap();
+        .then(
+            |game: SharedGame, action: Action, username: String, pass: String| async move {
+                let game = game.read().await;
                 if let Err(err) = game.authenticate(username.as_str(), pass.as_str()) {
-                    return warp::reply::json(&Empty::Error(err.to_string()));
+                    return error_reply(&game, err);
                 }
 
                 if let Ok(resp) = get_game_status(&game) {
                     return resp;
                 }
-                match game.donate_points(username.as_str(), req.receiver.as_str(), req.amount) {
-                    Ok(()) => warp::reply::json(&Empty::Ok),
+                // A rejected preview is what the player wanted to find out, it's not counted as
+                // an error
+                match game.preview(username.as_str(), &action) {
+                    Ok(preview) => warp::reply::json(&preview),
                     Err(err) => warp::reply::json(&Empty::Error(err.to_string())),
                 }
-            }
-        });
+            },
+        );
 
-    let build_action = warp::path("build-zord")
+    let orders_action = with_game
+        .clone()
+        .and(warp::path("orders"))
+        .and(warp::path::end())
+        .and(warp::header("username"))
+        .and(warp::header("token"))
+        .then(
+            |game: SharedGame, username: String, pass: String| async move {
+                let game = game.read().await;
+                if let Err(err) = game.authenticate(username.as_str(), pass.as_str()) {
+                    return error_reply(&game, err);
+                }
+                warp::reply::json(&OrderList {
+                    pending: game.orders.pending(username.as_str()).collect(),
+                    played: game.orders.played(username.as_str()).collect(),
+                })
+            },
+        );
+
+    let new_order_action = with_action_game
+        .clone()
+        .and(warp::path("orders"))
+        .and(warp::path("new"))
         .and(warp::body::json())
         .and(warp::header("username"))
         .and(warp::header("token"))
-        .map({
-            let game = game.clone();
-            move |req: SingleCoord, username: String, pass: String| {
-                let mut game = game.lock().unwrap();
+        .then(
+            |game: SharedGame, order: Order, username: String, pass: String| async move {
+                let mut game = game.write().await;
                 if let Err(err) = game.authenticate(username.as_str(), pass.as_str()) {
-                    return warp::reply::json(&Empty::Error(err.to_string()));
+                    return error_reply(&game, err);
                 }
 
                 if let Ok(resp) = get_game_status(&game) {
                     return resp;
                 }
-                let (x, y) = req.coord;
-                match game.build_zord(username.as_str(), x, y) {
-                    Ok(()) => warp::reply::json(&Empty::Ok),
-                    Err(err) => warp::reply::json(&Empty::Error(err.to_string())),
+                match game.orders.add(username.as_str(), order) {
+                    Ok(queued) => warp::reply::json(queued),
+                    Err(err) => error_reply(&game, err),
                 }
-            }
+            },
+        );
+
+    let cancel_order_action = with_action_game
+        .clone()
+        .and(warp::path("orders"))
+        .and(warp::path::param::<u64>())
+        .and(warp::path("cancel"))
+        .and(warp::header("username"))
+        .and(warp::header("token"))
+        .then(
+            |game: SharedGame, id: u64, username: String, pass: String| async move {
+                let mut game = game.write().await;
+                if let Err(err) = game.authenticate(username.as_str(), pass.as_str()) {
+                    return error_reply(&game, err);
+                }
+                match game.orders.cancel(username.as_str(), id) {
+                    Ok(_) => warp::reply::json(&Empty::Ok),
+                    Err(err) => error_reply(&game, err),
+                }
+            },
+        );
+
+    let map_action = with_game
+        .clone()
+        .and(warp::path("map"))
+        .and(warp::header::optional("username"))
+        .and(warp::header::optional("token"))
+        .then(
+            |game: SharedGame, username: Option<String>, pass: Option<String>| async move {
+                let game = game.read().await;
+                let sight = match viewer(&game, username, pass) {
+                    Ok(viewer) => Sight::new(&game, viewer.as_deref()),
+                    Err(err) => return error_reply(&game, err),
+                };
+                warp::reply::json(&WoopMap {
+                    zords: game
+                        .zords
+                        .iter()
+                        .filter(|zord| sight.sees((zord.x, zord.y)))
+                        .collect(),
+                    totems: (&game.totems.0, &game.totems.1),
+                })
+            },
+        );
+
+    let leaderboard_action =
+        with_game
+            .clone()
+            .and(warp::path("leaderboard"))
+            .then(|game: SharedGame| async move {
+                let game = game.read().await;
+                let mut lead: Vec<&Player> = game.players.values().collect();
+                lead.sort_by_key(|p| p.points);
+                lead.reverse();
+                warp::reply::json(&Leaderboard { leaderboard: &lead })
+            });
+
+    let me_action = with_game
+        .clone()
+        .and(warp::path("me"))
+        .and(warp::header("username"))
+        .and(warp::header("token"))
+        .then(
+            |game: SharedGame, username: String, pass: String| async move {
+                let game = game.read().await;
+                if let Err(err) = game.authenticate(username.as_str(), pass.as_str()) {
+                    return error_reply(&game, err);
+                }
+                let player = &game.players[username.as_str()];
+                warp::reply::json(&Me {
+                    player,
+                    zords: zord_tactics(&game, username.as_str()),
+                    within_grace_period: game.within_grace_period(),
+                    costs: ActionCosts::new(player.actions),
+                })
+            },
+        );
+
+    let stats_action = with_game
+        .clone()
+        .and(warp::path("stats"))
+        .and(warp::path::end())
+        .then(|game: SharedGame| async move {
+            let game = game.read().await;
+            let stats = game.logged_actions.stats();
+            let players = game
+                .players
+                .keys()
+                .map(|name| {
+                    let total = stats
+                        .player(name)
+                        .map(|record| record.total.clone())
+                        .unwrap_or_default();
+                    (name.clone(), total)
+                })
+                .collect();
+            warp::reply::json(&GameStats { players })
         });
 
-    let map_action = warp::path("map").map({
-        let game = game.clone();
-        move || {
-            let game = game.lock().unwrap();
-            warp::reply::json(&WoopMap {
-                zords: &game.zords,
-                totems: (&game.totems.0, &game.totems.1),
-            })
-        }
-    });
+    let player_stats_action = with_game
+        .clone()
+        .and(warp::path("stats"))
+        .and(warp::path::param::<String>())
+        .and(warp::path::end())
+        .then(|game: SharedGame, player: String| async move {
+            let game = game.read().await;
+            if !game.players.contains_key(player.as_str()) {
+                return error_reply(&game, WoopError::PlayerNotFound(player));
+            }
+            let record = game
+                .logged_actions
+                .stats()
+                .player(player.as_str())
+                .cloned()
+                .unwrap_or_default();
+            warp::reply::json(&record)
+        });
 
-    let leaderboard_action = warp::path("leaderboard").map({
-        let game = game.clone();
-        move || {
-            let game = game.lock().unwrap();
-            let mut lead: Vec<&Player> = game.players.values().collect();
-            lead.sort_by_key(|p| p.points);
-            lead.reverse();
-            warp::reply::json(&Leaderboard { leaderboard: &lead })
-        }
-    });
+    let graph_action = with_game
+        .clone()
+        .and(warp::path("graph"))
+        .and(warp::query::<GraphQuery>())
+        .then(|game: SharedGame, query: GraphQuery| async move {
+            let game = game.read().await;
+            let graph = game.logged_actions.graph();
+            let mut players: Vec<&String> = game.players.keys().collect();
+            players.sort();
+            match query.format {
+                GraphFormat::Json => warp::reply::json(&InteractionGraph {
+                    players,
+                    edges: graph.edges().collect(),
+                    betrayals: graph.betrayals(),
+                })
+                .into_response(),
+                GraphFormat::Dot => warp::reply::with_header(
+                    graph.to_dot(players.into_iter()),
+                    "Content-Type",
+                    "text/vnd.graphviz",
+                )
+                .into_response(),
+            }
+        });
 
-    let day_action = warp::path("day").map({
-        let game = game.clone();
-        move || {
-            let game = game.lock().unwrap();
+    let day_action = with_game
+        .clone()
+        .and(warp::path("day"))
+        .then(|game: SharedGame| async move {
+            let game = game.read().await;
             warp::reply::json(&GameInfo {
                 day: game.day,
                 start_of_day: game
@@ -210,49 +653,578 @@ pub async fn start_api(game: Arc<Mutex<Game>>) {
                     .unwrap()
                     .as_secs(),
             })
-        }
-    });
+        });
 
-    let log_action = warp::path("activity").and(warp::query::query()).map({
-        let game = game.clone();
-        move |q: HashMap<String, usize>| {
-            let game = game.lock().unwrap();
-            let chunk = q.get("chunk").copied().unwrap_or(0);
-            let data = game.logged_actions.get_chunk(chunk);
-            warp::reply::json(&Activity { activity: data })
-        }
-    });
+    let log_action = with_game
+        .clone()
+        .and(warp::path("activity"))
+        .and(warp::query::<EventQuery>())
+        .and(warp::header::optional("username"))
+        .and(warp::header::optional("token"))
+        .then(
+            |game: SharedGame,
+             query: EventQuery,
+             username: Option<String>,
+             pass: Option<String>| async move {
+                let game = game.read().await;
+                let sight = match viewer(&game, username, pass) {
+                    Ok(viewer) => Sight::new(&game, viewer.as_deref()),
+                    Err(err) => return error_reply(&game, err),
+                };
+                if let Err(err) = query.check() {
+                    return error_reply(&game, err);
+                }
+                let page = game
+                    .logged_actions
+                    .query_where(&query, |event| sight.sees_event(event));
+                warp::reply::json(&Activity {
+                    activity: page.events,
+                    before: page.before,
+                    after: page.after,
+                })
+            },
+        );
 
-    let auth_action = warp::path("auth")
+    let teams_action = with_game
+        .clone()
+        .and(warp::path("teams"))
+        .and(warp::path::end())
+        .then(|game: SharedGame| async move {
+            let game = game.read().await;
+            warp::reply::json(&TeamList {
+                teams: game.teams.iter().collect(),
+            })
+        });
+
+    let team_leaderboard_action = with_game
+        .clone()
+        .and(warp::path("teams"))
+        .and(warp::path("leaderboard"))
+        .and(warp::path::end())
+        .then(|game: SharedGame| async move {
+            let game = game.read().await;
+            let mut lead: Vec<TeamScore> = game
+                .teams
+                .iter()
+                .map(|team| TeamScore {
+                    name: team.name,
+                    members: team.members.iter().map(String::as_str).collect(),
+                    points: team
+                        .members
+                        .iter()
+                        .filter_map(|member| game.players.get(member))
+                        .map(|player| player.points as u32)
+                        .sum(),
+                })
+                .collect();
+            lead.sort_by_key(|team| std::cmp::Reverse(team.points));
+            warp::reply::json(&TeamLeaderboard { leaderboard: lead })
+        });
+
+    let new_team_action = with_action_game
+        .clone()
+        .and(warp::path("teams"))
+        .and(warp::path("new"))
+        .and(warp::path::end())
+        .and(warp::body::json())
+        .and(warp::header("username"))
+        .and(warp::header("token"))
+        .then(
+            |game: SharedGame, req: TeamName, username: String, pass: String| async move {
+                let mut game = game.write().await;
+                if let Err(err) = game.authenticate(username.as_str(), pass.as_str()) {
+                    return error_reply(&game, err);
+                }
+
+                if let Ok(resp) = get_game_status(&game) {
+                    return resp;
+                }
+                match game.create_team(username.as_str(), req.team.as_str()) {
+                    Ok(_) => warp::reply::json(&Empty::Ok),
+                    Err(err) => error_reply(&game, err),
+                }
+            },
+        );
+
+    let invite_action = with_action_game
+        .clone()
+        .and(warp::path("teams"))
+        .and(warp::path("invite"))
+        .and(warp::path::end())
+        .and(warp::body::json())
+        .and(warp::header("username"))
+        .and(warp::header("token"))
+        .then(
+            |game: SharedGame, req: Invite, username: String, pass: String| async move {
+                let mut game = game.write().await;
+                if let Err(err) = game.authenticate(username.as_str(), pass.as_str()) {
+                    return error_reply(&game, err);
+                }
+
+                if let Ok(resp) = get_game_status(&game) {
+                    return resp;
+                }
+                match game.invite_to_team(username.as_str(), req.player.as_str()) {
+                    Ok(_) => warp::reply::json(&Empty::Ok),
+                    Err(err) => error_reply(&game, err),
+                }
+            },
+        );
+
+    let accept_action = with_action_game
+        .clone()
+        .and(warp::path("teams"))
+        .and(warp::path("accept"))
+        .and(warp::path::end())
+        .and(warp::body::json())
         .and(warp::header("username"))
         .and(warp::header("token"))
-        .map({
-            let game = game.clone();
-            move |user: String, token: String| {
-                let game = game.lock().unwrap();
-                if let Err(err) = game.authenticate(user.as_str(), token.as_str()) {
-                    return warp::reply::json(&Empty::Error(err.to_string()));
-                }
-                warp::reply::json(&Empty::Ok)
+        .then(
+            |game: SharedGame, req: TeamName, username: String, pass: String| async move {
+                let mut game = game.write().await;
+                if let Err(err) = game.authenticate(username.as_str(), pass.as_str()) {
+                    return error_reply(&game, err);
+                }
+
+                if let Ok(resp) = get_game_status(&game) {
+                    return resp;
+                }
+                match game.join_team(username.as_str(), req.team.as_str()) {
+                    Ok(_) => warp::reply::json(&Empty::Ok),
+                    Err(err) => error_reply(&game, err),
+                }
+            },
+        );
+
+    let leave_action = with_action_game
+        .clone()
+        .and(warp::path("teams"))
+        .and(warp::path("leave"))
+        .and(warp::path::end())
+        .and(warp::header("username"))
+        .and(warp::header("token"))
+        .then(
+            |game: SharedGame, username: String, pass: String| async move {
+                let mut game = game.write().await;
+                if let Err(err) = game.authenticate(username.as_str(), pass.as_str()) {
+                    return error_reply(&game, err);
+                }
+
+                if let Ok(resp) = get_game_status(&game) {
+                    return resp;
+                }
+                match game.leave_team(username.as_str()) {
+                    Ok(_) => warp::reply::json(&Empty::Ok),
+                    Err(err) => error_reply(&game, err),
+                }
+            },
+        );
+
+    let treaties_action = with_game
+        .clone()
+        .and(warp::path("treaties"))
+        .and(warp::path::end())
+        .then(|game: SharedGame| async move {
+            let game = game.read().await;
+            warp::reply::json(&TreatyList {
+                treaties: game.treaties.iter().collect(),
+            })
+        });
+
+    let propose_treaty_action = with_action_game
+        .clone()
+        .and(warp::path("treaties"))
+        .and(warp::path("propose"))
+        .and(warp::path::end())
+        .and(warp::body::json())
+        .and(warp::header("username"))
+        .and(warp::header("token"))
+        .then(
+            |game: SharedGame, req: Proposal, username: String, pass: String| async move {
+                let mut game = game.write().await;
+                if let Err(err) = game.authenticate(username.as_str(), pass.as_str()) {
+                    return error_reply(&game, err);
+                }
+
+                if let Ok(resp) = get_game_status(&game) {
+                    return resp;
+                }
+                match game.propose_treaty(
+                    username.as_str(),
+                    req.counterpart.as_str(),
+                    req.gives,
+                    req.asks,
+                ) {
+                    Ok(treaty) => warp::reply::json(treaty),
+                    Err(err) => error_reply(&game, err),
+                }
+            },
+        );
+
+    let accept_treaty_action = with_action_game
+        .clone()
+        .and(warp::path("treaties"))
+        .and(warp::path::param::<u64>())
+        .and(warp::path("accept"))
+        .and(warp::path::end())
+        .and(warp::header("username"))
+        .and(warp::header("token"))
+        .then(
+            |game: SharedGame, id: u64, username: String, pass: String| async move {
+                let mut game = game.write().await;
+                if let Err(err) = game.authenticate(username.as_str(), pass.as_str()) {
+                    return error_reply(&game, err);
+                }
+
+                if let Ok(resp) = get_game_status(&game) {
+                    return resp;
+                }
+                match game.accept_treaty(username.as_str(), id) {
+                    Ok(_) => warp::reply::json(&Empty::Ok),
+                    Err(err) => error_reply(&game, err),
+                }
+            },
+        );
+
+    let reject_treaty_action = with_action_game
+        .clone()
+        .and(warp::path("treaties"))
+        .and(warp::path::param::<u64>())
+        .and(warp::path("reject"))
+        .and(warp::path::end())
+        .and(warp::header("username"))
+        .and(warp::header("token"))
+        .then(
+            |game: SharedGame, id: u64, username: String, pass: String| async move {
+                let mut game = game.write().await;
+                if let Err(err) = game.authenticate(username.as_str(), pass.as_str()) {
+                    return error_reply(&game, err);
+                }
+
+                if let Ok(resp) = get_game_status(&game) {
+                    return resp;
+                }
+                match game.reject_treaty(username.as_str(), id) {
+                    Ok(_) => warp::reply::json(&Empty::Ok),
+                    Err(err) => error_reply(&game, err),
+                }
+            },
+        );
+
+    let inbox_action = with_game
+        .clone()
+        .and(warp::path("messages"))
+        .and(warp::path::end())
+        .and(warp::header("username"))
+        .and(warp::header("token"))
+        .then(
+            |game: SharedGame, username: String, pass: String| async move {
+                let game = game.read().await;
+                if let Err(err) = game.authenticate(username.as_str(), pass.as_str()) {
+                    return error_reply(&game, err);
+                }
+                warp::reply::json(&game.messages.inbox(username.as_str()))
+            },
+        );
+
+    let send_message_action = with_action_game
+        .clone()
+        .and(warp::path("messages"))
+        .and(warp::path("send"))
+        .and(warp::path::end())
+        .and(warp::body::json())
+        .and(warp::header("username"))
+        .and(warp::header("token"))
+        .then(
+            |game: SharedGame, req: SendMessage, username: String, pass: String| async move {
+                let mut game = game.write().await;
+                if let Err(err) = game.authenticate(username.as_str(), pass.as_str()) {
+                    return error_reply(&game, err);
+                }
+                match game.send_message(username.as_str(), req.channel, req.body) {
+                    Ok(_) => warp::reply::json(&Empty::Ok),
+                    Err(err) => error_reply(&game, err),
+                }
+            },
+        );
+
+    let read_messages_action = with_action_game
+        .clone()
+        .and(warp::path("messages"))
+        .and(warp::path("read"))
+        .and(warp::path::end())
+        .and(warp::body::json())
+        .and(warp::header("username"))
+        .and(warp::header("token"))
+        .then(
+            |game: SharedGame, channel: Channel, username: String, pass: String| async move {
+                let mut game = game.write().await;
+                if let Err(err) = game.authenticate(username.as_str(), pass.as_str()) {
+                    return error_reply(&game, err);
+                }
+                match game.messages.read(username.as_str(), &channel) {
+                    Ok(messages) => warp::reply::json(&MessageList {
+                        messages: &messages,
+                    }),
+                    Err(err) => error_reply(&game, err),
+                }
+            },
+        );
+
+    let new_group_action = with_action_game
+        .clone()
+        .and(warp::path("messages"))
+        .and(warp::path("groups"))
+        .and(warp::path("new"))
+        .and(warp::path::end())
+        .and(warp::body::json())
+        .and(warp::header("username"))
+        .and(warp::header("token"))
+        .then(
+            |game: SharedGame, req: NewGroup, username: String, pass: String| async move {
+                let mut game = game.write().await;
+                if let Err(err) = game.authenticate(username.as_str(), pass.as_str()) {
+                    return error_reply(&game, err);
+                }
+                match game.create_group(username.as_str(), req.name.as_str(), &req.members) {
+                    Ok(_) => warp::reply::json(&Empty::Ok),
+                    Err(err) => error_reply(&game, err),
+                }
+            },
+        );
+
+    let join_group_action = with_action_game
+        .clone()
+        .and(warp::path("messages"))
+        .and(warp::path("groups"))
+        .and(warp::path("join"))
+        .and(warp::path::end())
+        .and(warp::body::json())
+        .and(warp::header("username"))
+        .and(warp::header("token"))
+        .then(
+            |game: SharedGame, req: GroupName, username: String, pass: String| async move {
+                let mut game = game.write().await;
+                if let Err(err) = game.authenticate(username.as_str(), pass.as_str()) {
+                    return error_reply(&game, err);
+                }
+                match game
+                    .messages
+                    .join_group(req.group.as_str(), username.as_str())
+                {
+                    Ok(_) => warp::reply::json(&Empty::Ok),
+                    Err(err) => error_reply(&game, err),
+                }
+            },
+        );
+
+    let all_messages_action = with_game
+        .clone()
+        .and(warp::path("messages"))
+        .and(warp::path("all"))
+        .and(warp::path::end())
+        .then(|game: SharedGame| async move {
+            let game = game.read().await;
+            match game.revealed_messages() {
+                Ok(messages) => warp::reply::json(&MessageList { messages }),
+                Err(err) => error_reply(&game, err),
             }
         });
 
-    // Static pages
-    let index_static = warp::path("index.html").map(|| {
-        let body = fs::read("static/index.html").unwrap();
-        warp::reply::html(body)
-    });
-    let index_empty_static = warp::path::end().map(|| {
-        let body = fs::read("static/index.html").unwrap();
-        warp::reply::html(body)
+    let stream_action = warp::get()
+        .and(with_game.clone())
+        .and(warp::path("stream"))
+        .and(warp::path::end())
+        .and(warp::query::<StreamQuery>())
+        .and(warp::header::optional("username"))
+        .and(warp::header::optional("token"))
+        .then({
+            let stop_streams = stop_streams.clone();
+            move |game: SharedGame,
+                  query: StreamQuery,
+                  username: Option<String>,
+                  pass: Option<String>| {
+                let stop = stop_streams.subscribe();
+                async move {
+                    let authenticated = match query.ticket {
+                        Some(ticket) => game
+                            .write()
+                            .await
+                            .redeem_stream_ticket(ticket.as_str())
+                            .map(Some),
+                        None => viewer(&*game.read().await, username, pass),
+                    };
+                    let (viewer, live) = {
+                        let game = game.read().await;
+                        match authenticated {
+                            Ok(viewer) => (viewer, game.logged_actions.feed().subscribe()),
+                            Err(err) => return error_reply(&game, err).into_response(),
+                        }
+                    };
+                    // Whatever happens from now on that the viewer is allowed to see
+                    let events = futures_util::stream::unfold(
+                        (game, live, stop, viewer),
+                        |(game, mut live, mut stop, viewer)| async move {
+                            loop {
+                                let item = tokio::select! {
+                                    item = live.recv() => item,
+                                    _ = stop.recv() => return None,
+                                };
+                                let item = match item {
+                                    Ok(item) => item,
+                                    Err(RecvError::Lagged(_)) => continue,
+                                    Err(RecvError::Closed) => return None,
+                                };
+                                if game.read().await.sees_live(viewer.as_deref(), &item) {
+                                    let event =
+                                        Event::default().event(item.kind()).json_data(&item);
+                                    return Some((event, (game, live, stop, viewer)));
+                                }
+                            }
+                        },
+                    );
+                    warp::sse::reply(warp::sse::keep_alive().stream(events)).into_response()
+                }
+            }
+        });
+
+    let stream_ticket_action = with_game
+        .clone()
+        .and(warp::path("stream"))
+        .and(warp::path("ticket"))
+        .and(warp::path::end())
+        .and(warp::header("username"))
+        .and(warp::header("token"))
+        .then(
+            |game: SharedGame, username: String, pass: String| async move {
+                let mut game = game.write().await;
+                if let Err(err) = game.authenticate(username.as_str(), pass.as_str()) {
+                    return error_reply(&game, err);
+                }
+                warp::reply::json(&game.stream_ticket(username.as_str()))
+            },
+        );
+
+    let with_spectator = with_game
+        .clone()
+        .and(warp::path("spectate"))
+        .and(warp::header("username"))
+        .and(warp::header("token"));
+
+    let spectate_board_action = with_spectator
+        .clone()
+        .and(warp::path("board"))
+        .and(warp::path::end())
+        .then(|game: SharedGame, user: String, token: String| async move {
+            let game = game.read().await;
+            if let Err(err) = game.authenticate_spectator(user.as_str(), token.as_str()) {
+                return error_reply(&game, err);
+            }
+            warp::reply::json(&board_at(&game, game.spectator_time()))
+        });
+
+    let spectate_log_action = with_spectator
+        .clone()
+        .and(warp::path("activity"))
+        .and(warp::query::<EventQuery>())
+        .then(
+            |game: SharedGame, user: String, token: String, query: EventQuery| async move {
+                let game = game.read().await;
+                if let Err(err) = game.authenticate_spectator(user.as_str(), token.as_str()) {
+                    return error_reply(&game, err);
+                }
+                if let Err(err) = query.check() {
+                    return error_reply(&game, err);
+                }
+                let time = game.spectator_time();
+                let page = game
+                    .logged_actions
+                    .query_where(&query, |event| event.timestamp() <= time);
+                warp::reply::json(&Activity {
+                    activity: page.events,
+                    before: page.before,
+                    after: page.after,
+                })
+            },
+        );
+
+    let auth_action = with_game
+        .clone()
+        .and(warp::path("auth"))
+        .and(warp::header("username"))
+        .and(warp::header("token"))
+        .then(|game: SharedGame, user: String, token: String| async move {
+            let game = game.read().await;
+            if let Err(err) = game.authenticate(user.as_str(), token.as_str()) {
+                return error_reply(&game, err);
+            }
+            warp::reply::json(&Empty::Ok)
+        });
+
+    let games_action = warp::path("games").and(warp::path::end()).then({
+        let registry = registry.clone();
+        move || {
+            let registry = registry.clone();
+            async move {
+                let mut games = Vec::new();
+                for (id, entry) in registry.iter() {
+                    let game = entry.game.read().await;
+                    games.push(GameSummary {
+                        id: id.clone(),
+                        day: game.day,
+                        players: game.players.len(),
+                    });
+                }
+                warp::reply::json(&GameList { games })
+            }
+        }
     });
-    let style_static = warp::path("style.css").map(|| {
-        let body = fs::read("static/style.css").unwrap();
-        warp::reply::html(body)
+
+    let metrics_action = warp::path("metrics").and(warp::get()).then({
+        let registry = registry.clone();
+        move || {
+            let registry = registry.clone();
+            async move {
+                warp::reply::with_header(
+                    METRICS.render(&registry).await,
+                    "Content-Type",
+                    "text/plain; version=0.0.4",
+                )
+            }
+        }
     });
-    let js_static = warp::path("client.js").map(|| {
-        let body = fs::read("static/client.js").unwrap();
-        warp::reply::html(body)
+
+    let healthz_action = warp::path("healthz")
+        .and(warp::get())
+        .map(|| warp::reply::json(&Empty::Ok));
+
+    let readyz_action = warp::path("readyz").and(warp::get()).then({
+        let registry = registry.clone();
+        move || {
+            let registry = registry.clone();
+            async move {
+                let max_tick_age = TICK_INTERVAL.as_secs() * 3;
+                let mut games = Vec::new();
+                for (id, entry) in registry.iter() {
+                    let game = entry.game.read().await;
+                    games.push(GameHealth {
+                        id: id.clone(),
+                        phase: game.phase(),
+                        ready: entry.status.is_ready(max_tick_age),
+                        scheduler_alive: entry.status.scheduler_alive(),
+                        last_tick: entry.status.last_tick(),
+                        last_rollover: entry.status.last_rollover(),
+                        persistence: entry.status.persistence(),
+                    });
+                }
+                let ready = games.iter().all(|game| game.ready);
+                let status = match ready {
+                    true => StatusCode::OK,
+                    false => StatusCode::SERVICE_UNAVAILABLE,
+                };
+                warp::reply::with_status(warp::reply::json(&Readiness { ready, games }), status)
+            }
+        }
     });
 
     let docs = warp::path("docs")
@@ -261,37 +1233,90 @@ pub async fn start_api(game: Arc<Mutex<Game>>) {
     let rapidoc = warp::path("rapidoc")
         .and(warp::get())
         .map(|| warp::reply::html(RapiDoc::new("/docs").to_html()));
-    let static_pages = warp::get().and(
-        index_static
-            .or(index_empty_static)
-            .or(style_static)
-            .or(js_static),
-    );
-
-    let cors =
-        warp::cors()
-            .allow_method("POST")
-            .allow_headers(["username", "token", "Content-Type"]);
+    let cors = warp::cors().allow_method("POST").allow_headers([
+        "username",
+        "token",
+        "idempotency-key",
+        "Content-Type",
+    ]);
     let logger = warp::log("api::api");
+    let metrics = warp::log::custom(|info| {
+        METRICS.observe_request(info.path(), info.status().as_u16(), info.elapsed())
+    });
+    // Boxed in groups, a single chain of this many filters is too deep for the compiler
+    let action_routes = shoot_action
+        .or(move_action)
+        .or(shield_action)
+        .or(increase_action)
+        .or(donate_action)
+        .or(build_action)
+        .or(batch_action)
+        .or(preview_action)
+        .or(orders_action)
+        .or(new_order_action)
+        .or(cancel_order_action)
+        .boxed();
+    let message_routes = inbox_action
+        .or(send_message_action)
+        .or(read_messages_action)
+        .or(new_group_action)
+        .or(join_group_action)
+        .or(all_messages_action)
+        .boxed();
+    let team_routes = teams_action
+        .or(team_leaderboard_action)
+        .or(new_team_action)
+        .or(invite_action)
+        .or(accept_action)
+        .or(leave_action)
+        .boxed();
+    let treaty_routes = treaties_action
+        .or(propose_treaty_action)
+        .or(accept_treaty_action)
+        .or(reject_treaty_action)
+        .boxed();
+    let view_routes = map_action
+        .or(leaderboard_action)
+        .or(me_action)
+        .or(stats_action)
+        .or(player_stats_action)
+        .or(graph_action)
+        .or(day_action)
+        .or(log_action)
+        .or(spectate_board_action)
+        .or(spectate_log_action)
+        .or(auth_action)
+        .or(games_action)
+        .boxed();
+
     let routes = warp::post()
         .and(
-            shoot_action
-                .or(move_action)
-                .or(shield_action)
-                .or(increase_action)
-                .or(donate_action)
-                .or(build_action)
-                .or(map_action)
-                .or(leaderboard_action)
-                .or(day_action)
-                .or(log_action)
-                .or(auth_action),
+            action_routes
+                .or(team_routes)
+                .or(treaty_routes)
+                .or(message_routes)
+                .or(view_routes),
         )
+        .or(stream_action)
+        .or(stream_ticket_action)
+        .or(metrics_action)
+        .or(healthz_action)
+        .or(readyz_action)
         .or(docs)
         .or(rapidoc)
-        .or(static_pages)
+        .or(static_files())
         .recover(handle_rejection)
         .with(cors)
-        .with(logger);
-    warp::serve(routes).run(([0, 0, 0, 0], 6969)).await;
+        .with(logger)
+        .with(metrics);
+    warp::serve(routes)
+        .bind(([0, 0, 0, 0], 6969))
+        .await
+        .graceful(async move {
+            shutdown.await;
+            closing.store(true, Ordering::SeqCst);
+            let _ = stop_streams.send(());
+        })
+        .run()
+        .await;
 }
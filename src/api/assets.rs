@@ -0,0 +1,147 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use sha2::{Digest, Sha256};
+use std::borrow::Cow;
+use std::convert::Infallible;
+use warp::http::header::{CACHE_CONTROL, CONTENT_TYPE, ETAG};
+use warp::http::{HeaderValue, StatusCode};
+use warp::reply::Response;
+use warp::Filter;
+use warp::Reply as WarpReply;
+
+// Embedded assets never change for the lifetime of the binary, while the ones read from disk are
+// expected to be rebuilt while the server is running
+#[cfg(feature = "embed-assets")]
+const CACHE_POLICY: &str = "public, max-age=3600";
+#[cfg(not(feature = "embed-assets"))]
+const CACHE_POLICY: &str = "no-cache";
+
+const ASSETS: [(&str, &str); 3] = [
+    ("index.html", "text/html; charset=utf-8"),
+    ("style.css", "text/css; charset=utf-8"),
+    ("client.js", "text/javascript; charset=utf-8"),
+];
+
+#[cfg(feature = "embed-assets")]
+fn load(name: &str) -> Option<Cow<'static, [u8]>> {
+    let body: &'static [u8] = match name {
+        "index.html" => include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/static/index.html")),
+        "style.css" => include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/static/style.css")),
+        "client.js" => include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/static/client.js")),
+        _ => return None,
+    };
+    Some(Cow::Borrowed(body))
+}
+
+#[cfg(not(feature = "embed-assets"))]
+fn load(name: &str) -> Option<Cow<'static, [u8]>> {
+    std::fs::read(std::path::Path::new("static").join(name))
+        .ok()
+        .map(Cow::Owned)
+}
+
+fn etag(body: &[u8]) -> String {
+    let digest = Sha256::digest(body);
+    format!("\"{}\"", URL_SAFE_NO_PAD.encode(&digest[..16]))
+}
+
+// `If-None-Match` holds a comma separated list of tags, any of which may be weak. The body of a
+// static file is all there is to it, so weak tags are compared like strong ones.
+fn matches(if_none_match: &str, tag: &str) -> bool {
+    if_none_match.split(',').map(str::trim).any(|candidate| {
+        candidate == "*" || candidate.strip_prefix("W/").unwrap_or(candidate) == tag
+    })
+}
+
+fn serve(name: String, if_none_match: Option<String>) -> Response {
+    let (name, mime) = match ASSETS.iter().find(|(asset, _)| *asset == name.as_str()) {
+        Some(asset) => *asset,
+        None => return StatusCode::NOT_FOUND.into_response(),
+    };
+    let body = match load(name) {
+        Some(body) => body,
+        None => {
+            log::error!("Couldn't load static asset {}", name);
+            return StatusCode::NOT_FOUND.into_response();
+        }
+    };
+
+    respond(mime, body, if_none_match)
+}
+
+fn respond(
+    mime: &'static str,
+    body: Cow<'static, [u8]>,
+    if_none_match: Option<String>,
+) -> Response {
+    let tag = etag(&body);
+    let mut resp = match if_none_match {
+        Some(tags) if matches(tags.as_str(), tag.as_str()) => {
+            StatusCode::NOT_MODIFIED.into_response()
+        }
+        _ => {
+            let mut resp = body.into_owned().into_response();
+            resp.headers_mut()
+                .insert(CONTENT_TYPE, HeaderValue::from_static(mime));
+            resp
+        }
+    };
+    let headers = resp.headers_mut();
+    headers.insert(CACHE_CONTROL, HeaderValue::from_static(CACHE_POLICY));
+    headers.insert(ETAG, HeaderValue::from_str(tag.as_str()).unwrap());
+    resp
+}
+
+/// Serves the web client. With the `embed-assets` feature the files are baked into the binary,
+/// otherwise they are read from the `static` directory on every request.
+pub fn static_files() -> impl Filter<Extract = (Response,), Error = warp::Rejection> + Clone {
+    let index = warp::path::end().map(|| String::from("index.html"));
+    let file = warp::path::param::<String>().and(warp::path::end());
+    warp::get()
+        .and(index.or(file).unify())
+        .and(warp::header::optional::<String>("if-none-match"))
+        .and_then(|name: String, tag: Option<String>| async move {
+            Ok::<_, Infallible>(serve(name, tag))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{etag, matches, respond};
+    use std::borrow::Cow;
+    use warp::http::header::ETAG;
+    use warp::http::StatusCode;
+
+    #[test]
+    fn etag_is_stable() {
+        assert_eq!(etag(b"woop"), etag(b"woop"));
+        assert_ne!(etag(b"woop"), etag(b"attack"));
+    }
+
+    #[test]
+    fn if_none_match_lists() {
+        let tag = etag(b"woop");
+        assert!(matches(tag.as_str(), tag.as_str()));
+        assert!(matches(format!("W/{}", tag).as_str(), tag.as_str()));
+        assert!(matches(
+            format!("\"old\", W/{}", tag).as_str(),
+            tag.as_str()
+        ));
+        assert!(matches("*", tag.as_str()));
+        assert!(!matches("\"old\", W/\"older\"", tag.as_str()));
+    }
+
+    #[test]
+    fn not_modified_round_trip() {
+        let page = || Cow::Borrowed(&b"<html></html>"[..]);
+        let first = respond("text/html", page(), None);
+        assert_eq!(first.status(), StatusCode::OK);
+        let tag = first.headers()[ETAG].to_str().unwrap().to_string();
+
+        let again = respond("text/html", page(), Some(format!("W/{}", tag)));
+        assert_eq!(again.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(again.headers()[ETAG].to_str().unwrap(), tag);
+
+        let stale = respond("text/html", page(), Some(String::from("\"stale\"")));
+        assert_eq!(stale.status(), StatusCode::OK);
+    }
+}
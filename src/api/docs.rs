@@ -1,6 +1,17 @@
 use crate::api::message::{
-    Activity, Donate, DoubleCoord, Empty, GameInfo, Leaderboard, SingleCoord, WoopMap,
+    Activity, Batch, BatchResult, Donate, DoubleCoord, Empty, GameInfo, GameList, GameStats,
+    GraphQuery, GroupName, InteractionGraph, Invite, Leaderboard, Me, MessageList, NewGroup,
+    OrderList, Proposal, SendMessage, SingleCoord, StreamQuery, TeamLeaderboard, TeamList,
+    TeamName, TreatyList, WoopMap,
 };
+use crate::game::action::Action;
+use crate::game::game::{Preview, StreamTicket};
+use crate::game::log::EventQuery;
+use crate::game::messages::{Channel, Inbox};
+use crate::game::orders::{Order, QueuedOrder};
+use crate::game::replay::PastBoard;
+use crate::game::stats::PlayerRecord;
+use crate::game::treaty::{Term, Treaty};
 use utoipa::OpenApi;
 
 #[derive(OpenApi)]
@@ -12,11 +23,41 @@ use utoipa::OpenApi;
         crate::api::docs::increase_range,
         crate::api::docs::donate_points,
         crate::api::docs::build_zord,
+        crate::api::docs::batch,
+        crate::api::docs::preview,
+        crate::api::docs::list_orders,
+        crate::api::docs::new_order,
+        crate::api::docs::cancel_order,
         crate::api::docs::get_board_data,
         crate::api::docs::get_day,
         crate::api::docs::get_activity,
+        crate::api::docs::spectate_board,
+        crate::api::docs::spectate_activity,
         crate::api::docs::leaderboard,
+        crate::api::docs::me,
+        crate::api::docs::list_teams,
+        crate::api::docs::team_leaderboard,
+        crate::api::docs::new_team,
+        crate::api::docs::invite_to_team,
+        crate::api::docs::join_team,
+        crate::api::docs::leave_team,
+        crate::api::docs::list_treaties,
+        crate::api::docs::propose_treaty,
+        crate::api::docs::accept_treaty,
+        crate::api::docs::reject_treaty,
+        crate::api::docs::inbox,
+        crate::api::docs::send_message,
+        crate::api::docs::read_messages,
+        crate::api::docs::new_group,
+        crate::api::docs::join_group,
+        crate::api::docs::all_messages,
+        crate::api::docs::stream,
+        crate::api::docs::stream_ticket,
+        crate::api::docs::get_stats,
+        crate::api::docs::get_player_stats,
+        crate::api::docs::get_graph,
         crate::api::docs::authenticate,
+        crate::api::docs::list_games,
     ),
     components(schemas(
         crate::game::zord::Zord,
@@ -25,12 +66,56 @@ use utoipa::OpenApi;
         crate::api::message::DoubleCoord,
         crate::api::message::SingleCoord,
         crate::api::message::Donate,
+        crate::game::action::Action,
+        crate::api::message::Batch,
+        crate::api::message::Step,
+        crate::api::message::BatchResult,
+        crate::game::orders::Order,
+        crate::game::orders::QueuedOrder,
+        crate::game::orders::PlayedOrder,
+        crate::api::message::OrderList,
+        crate::game::game::Preview,
         crate::api::message::WoopMap,
         crate::api::message::Leaderboard,
+        crate::api::message::TeamName,
+        crate::api::message::Invite,
+        crate::api::message::TeamList,
+        crate::game::team::Team,
+        crate::api::message::TeamScore,
+        crate::api::message::TeamLeaderboard,
+        crate::game::treaty::Term,
+        crate::game::treaty::TreatyStatus,
+        crate::game::treaty::Escrow,
+        crate::game::treaty::Treaty,
+        crate::api::message::Proposal,
+        crate::api::message::TreatyList,
+        crate::game::messages::Channel,
+        crate::game::messages::Message,
+        crate::game::messages::Conversation,
+        crate::game::messages::Inbox,
+        crate::api::message::SendMessage,
+        crate::api::message::NewGroup,
+        crate::api::message::GroupName,
+        crate::api::message::MessageList,
+        crate::game::game::StreamTicket,
+        crate::api::message::Me,
+        crate::game::tactics::ZordTactics,
+        crate::game::tactics::ActionCosts,
         crate::api::message::GameInfo,
         crate::api::message::Activity,
+        crate::game::replay::PastBoard,
         crate::api::message::Empty,
+        crate::api::message::GameSummary,
+        crate::api::message::GameList,
+        crate::api::message::GameStats,
         crate::game::log::PlayerEvent,
+        crate::game::stats::PlayerStats,
+        crate::game::stats::PlayerRecord,
+        crate::api::message::GraphFormat,
+        crate::api::message::InteractionGraph,
+        crate::game::graph::Interactions,
+        crate::game::graph::Edge,
+        crate::game::graph::Betrayal,
     ))
 )]
 pub struct ApiDoc;
@@ -39,14 +124,21 @@ pub struct ApiDoc;
 #[allow(dead_code)]
 #[utoipa::path(
     post,
-    path = "/shoot",
+    path = "/games/{id}/shoot",
     request_body(
         content = DoubleCoord,
         example = json!(DoubleCoord {from: (0, 0), to: (1, 1)}),
     ),
     params(
+        ("id" = String, Path, example = json!(String::from("woop"))),
         ("username" = String, Header, example = json!(String::from("mirko.faina"))),
         ("token" = String, Header, example = json!(String::from("this_is_a_token"))),
+        (
+            "idempotency-key" = Option<String>,
+            Header,
+            description = "Retries with the same key get the original reply until rollover",
+            example = json!(String::from("4e8d0c4a")),
+        ),
     ),
     responses(
         (status = 200, body = Empty),
@@ -58,14 +150,21 @@ pub async fn shoot() {}
 #[allow(dead_code)]
 #[utoipa::path(
     post,
-    path = "/move",
+    path = "/games/{id}/move",
     request_body(
         content = DoubleCoord,
         example = json!(DoubleCoord {from: (0, 0), to: (1, 1)}),
     ),
     params(
+        ("id" = String, Path, example = json!(String::from("woop"))),
         ("username" = String, Header, example = json!(String::from("mirko.faina"))),
         ("token" = String, Header, example = json!(String::from("this_is_a_token"))),
+        (
+            "idempotency-key" = Option<String>,
+            Header,
+            description = "Retries with the same key get the original reply until rollover",
+            example = json!(String::from("4e8d0c4a")),
+        ),
     ),
     responses(
         (status = 200, body = Empty)
@@ -77,14 +176,21 @@ pub async fn move_() {}
 #[allow(dead_code)]
 #[utoipa::path(
     post,
-    path = "/shield",
+    path = "/games/{id}/shield",
     request_body(
         content = SingleCoord,
         example = json!(SingleCoord {coord: (0, 0)}),
     ),
     params(
+        ("id" = String, Path, example = json!(String::from("woop"))),
         ("username" = String, Header, example = json!(String::from("mirko.faina"))),
         ("token" = String, Header, example = json!(String::from("this_is_a_token"))),
+        (
+            "idempotency-key" = Option<String>,
+            Header,
+            description = "Retries with the same key get the original reply until rollover",
+            example = json!(String::from("4e8d0c4a")),
+        ),
     ),
     responses(
         (status = 200, body = Empty),
@@ -96,14 +202,21 @@ pub async fn shield() {}
 #[allow(dead_code)]
 #[utoipa::path(
     post,
-    path = "/increase-range",
+    path = "/games/{id}/increase-range",
     request_body(
         content = SingleCoord,
         example = json!(SingleCoord {coord: (0, 0)}),
     ),
     params(
+        ("id" = String, Path, example = json!(String::from("woop"))),
         ("username" = String, Header, example = json!(String::from("mirko.faina"))),
         ("token" = String, Header, example = json!(String::from("this_is_a_token"))),
+        (
+            "idempotency-key" = Option<String>,
+            Header,
+            description = "Retries with the same key get the original reply until rollover",
+            example = json!(String::from("4e8d0c4a")),
+        ),
     ),
     responses(
         (status = 200, body = Empty),
@@ -115,14 +228,21 @@ pub async fn increase_range() {}
 #[allow(dead_code)]
 #[utoipa::path(
     post,
-    path = "/donate-points",
+    path = "/games/{id}/donate-points",
     request_body(
         content = Donate,
         example = json!(Donate { receiver: String::from("fin"), amount: 10 }),
     ),
     params(
+        ("id" = String, Path, example = json!(String::from("woop"))),
         ("username" = String, Header, example = json!(String::from("mirko.faina"))),
         ("token" = String, Header, example = json!(String::from("this_is_a_token"))),
+        (
+            "idempotency-key" = Option<String>,
+            Header,
+            description = "Retries with the same key get the original reply until rollover",
+            example = json!(String::from("4e8d0c4a")),
+        ),
     ),
     responses(
         (status = 200, body = Empty),
@@ -134,14 +254,21 @@ pub async fn donate_points() {}
 #[allow(dead_code)]
 #[utoipa::path(
     post,
-    path = "/build-zord",
+    path = "/games/{id}/build-zord",
     request_body(
         content = SingleCoord,
         example = json!(SingleCoord {coord: (0, 0)}),
     ),
     params(
+        ("id" = String, Path, example = json!(String::from("woop"))),
         ("username" = String, Header, example = json!(String::from("mirko.faina"))),
         ("token" = String, Header, example = json!(String::from("this_is_a_token"))),
+        (
+            "idempotency-key" = Option<String>,
+            Header,
+            description = "Retries with the same key get the original reply until rollover",
+            example = json!(String::from("4e8d0c4a")),
+        ),
     ),
     responses(
         (status = 200, body = Empty),
@@ -149,32 +276,582 @@ pub async fn donate_points() {}
 )]
 pub async fn build_zord() {}
 
+/// Play several actions in a row, either all of them or none
+#[allow(dead_code)]
+#[utoipa::path(
+    post,
+    path = "/games/{id}/batch",
+    request_body(
+        content = Batch,
+        example = json!(Batch {
+            actions: vec![
+                Action::Move {from: (0, 0), to: (2, 2)},
+                Action::Shield {coord: (2, 2)},
+                Action::Shoot {from: (2, 2), to: (3, 3)},
+            ],
+            best_effort: false,
+        }),
+    ),
+    params(
+        ("id" = String, Path, example = json!(String::from("woop"))),
+        ("username" = String, Header, example = json!(String::from("mirko.faina"))),
+        ("token" = String, Header, example = json!(String::from("this_is_a_token"))),
+        (
+            "idempotency-key" = Option<String>,
+            Header,
+            description = "Retries with the same key get the original reply until rollover",
+            example = json!(String::from("4e8d0c4a")),
+        ),
+    ),
+    responses(
+        (status = 200, body = BatchResult),
+    ),
+)]
+pub async fn batch() {}
+
+/// Check what an action would cost and do without playing it
+#[allow(dead_code)]
+#[utoipa::path(
+    post,
+    path = "/games/{id}/preview",
+    request_body(
+        content = Action,
+        example = json!(Action::Shoot {from: (0, 0), to: (1, 1)}),
+    ),
+    params(
+        ("id" = String, Path, example = json!(String::from("woop"))),
+        ("username" = String, Header, example = json!(String::from("mirko.faina"))),
+        ("token" = String, Header, example = json!(String::from("this_is_a_token"))),
+    ),
+    responses(
+        (status = 200, body = Preview),
+    ),
+)]
+pub async fn preview() {}
+
+/// Get your orders waiting to be played and the ones played today
+#[allow(dead_code)]
+#[utoipa::path(
+    post,
+    path = "/games/{id}/orders",
+    params(
+        ("id" = String, Path, example = json!(String::from("woop"))),
+        ("username" = String, Header, example = json!(String::from("mirko.faina"))),
+        ("token" = String, Header, example = json!(String::from("this_is_a_token"))),
+    ),
+    responses(
+        (status = 200, body = OrderList),
+    ),
+)]
+pub async fn list_orders() {}
+
+/// Leave an action for the server to play at a given time or when an enemy comes close
+#[allow(dead_code)]
+#[utoipa::path(
+    post,
+    path = "/games/{id}/orders/new",
+    request_body(
+        content = Order,
+        example = json!(Order::ShootInRange {coord: (0, 0)}),
+    ),
+    params(
+        ("id" = String, Path, example = json!(String::from("woop"))),
+        ("username" = String, Header, example = json!(String::from("mirko.faina"))),
+        ("token" = String, Header, example = json!(String::from("this_is_a_token"))),
+    ),
+    responses(
+        (status = 200, body = QueuedOrder),
+    ),
+)]
+pub async fn new_order() {}
+
+/// Cancel an order that wasn't played yet
+#[allow(dead_code)]
+#[utoipa::path(
+    post,
+    path = "/games/{id}/orders/{order}/cancel",
+    params(
+        ("id" = String, Path, example = json!(String::from("woop"))),
+        ("order" = u64, Path, example = json!(0)),
+        ("username" = String, Header, example = json!(String::from("mirko.faina"))),
+        ("token" = String, Header, example = json!(String::from("this_is_a_token"))),
+    ),
+    responses(
+        (status = 200, body = Empty),
+    ),
+)]
+pub async fn cancel_order() {}
+
 /// Get entities present on the game board
 #[allow(dead_code)]
-#[utoipa::path(post, path = "/map", responses((status = 200, body = WoopMap),))]
+#[utoipa::path(
+    post,
+    path = "/games/{id}/map",
+    params(
+        ("id" = String, Path, example = json!(String::from("woop"))),
+        (
+            "username" = Option<String>,
+            Header,
+            description = "Needed to see the zords when the game limits the vision",
+            example = json!(String::from("mirko.faina")),
+        ),
+        ("token" = Option<String>, Header, example = json!(String::from("this_is_a_token"))),
+    ),
+    responses((status = 200, body = WoopMap)),
+)]
 pub async fn get_board_data() {}
 
 /// Get list of players sorted by points
 #[allow(dead_code)]
-#[utoipa::path(post, path = "/leaderboard", responses((status = 200, body = Leaderboard),))]
+#[utoipa::path(
+    post,
+    path = "/games/{id}/leaderboard",
+    params(("id" = String, Path, example = json!(String::from("woop")))),
+    responses((status = 200, body = Leaderboard)),
+)]
 pub async fn leaderboard() {}
 
+/// Get your player, your zords with the enemies in range and threatening them, and the cost of
+/// each action
+#[allow(dead_code)]
+#[utoipa::path(
+    post,
+    path = "/games/{id}/me",
+    params(
+        ("id" = String, Path, example = json!(String::from("woop"))),
+        ("username" = String, Header, example = json!(String::from("mirko.faina"))),
+        ("token" = String, Header, example = json!(String::from("this_is_a_token"))),
+    ),
+    responses(
+        (status = 200, body = Me),
+    ),
+)]
+pub async fn me() {}
+
+/// Get the teams, their members and the players invited to join them
+#[allow(dead_code)]
+#[utoipa::path(
+    post,
+    path = "/games/{id}/teams",
+    params(("id" = String, Path, example = json!(String::from("woop")))),
+    responses((status = 200, body = TeamList)),
+)]
+pub async fn list_teams() {}
+
+/// Get the teams sorted by the points of their members
+#[allow(dead_code)]
+#[utoipa::path(
+    post,
+    path = "/games/{id}/teams/leaderboard",
+    params(("id" = String, Path, example = json!(String::from("woop")))),
+    responses((status = 200, body = TeamLeaderboard)),
+)]
+pub async fn team_leaderboard() {}
+
+/// Start a new team, you must not be in one already
+#[allow(dead_code)]
+#[utoipa::path(
+    post,
+    path = "/games/{id}/teams/new",
+    request_body(
+        content = TeamName,
+        example = json!(TeamName {team: String::from("red")}),
+    ),
+    params(
+        ("id" = String, Path, example = json!(String::from("woop"))),
+        ("username" = String, Header, example = json!(String::from("mirko.faina"))),
+        ("token" = String, Header, example = json!(String::from("this_is_a_token"))),
+    ),
+    responses(
+        (status = 200, body = Empty),
+    ),
+)]
+pub async fn new_team() {}
+
+/// Invite a player to join your team
+#[allow(dead_code)]
+#[utoipa::path(
+    post,
+    path = "/games/{id}/teams/invite",
+    request_body(
+        content = Invite,
+        example = json!(Invite {player: String::from("fin")}),
+    ),
+    params(
+        ("id" = String, Path, example = json!(String::from("woop"))),
+        ("username" = String, Header, example = json!(String::from("mirko.faina"))),
+        ("token" = String, Header, example = json!(String::from("this_is_a_token"))),
+    ),
+    responses(
+        (status = 200, body = Empty),
+    ),
+)]
+pub async fn invite_to_team() {}
+
+/// Join a team you were invited to, you must leave your current team first
+#[allow(dead_code)]
+#[utoipa::path(
+    post,
+    path = "/games/{id}/teams/accept",
+    request_body(
+        content = TeamName,
+        example = json!(TeamName {team: String::from("red")}),
+    ),
+    params(
+        ("id" = String, Path, example = json!(String::from("woop"))),
+        ("username" = String, Header, example = json!(String::from("mirko.faina"))),
+        ("token" = String, Header, example = json!(String::from("this_is_a_token"))),
+    ),
+    responses(
+        (status = 200, body = Empty),
+    ),
+)]
+pub async fn join_team() {}
+
+/// Leave your team, a team is disbanded once its last member leaves
+#[allow(dead_code)]
+#[utoipa::path(
+    post,
+    path = "/games/{id}/teams/leave",
+    params(
+        ("id" = String, Path, example = json!(String::from("woop"))),
+        ("username" = String, Header, example = json!(String::from("mirko.faina"))),
+        ("token" = String, Header, example = json!(String::from("this_is_a_token"))),
+    ),
+    responses(
+        (status = 200, body = Empty),
+    ),
+)]
+pub async fn leave_team() {}
+
+/// Get every treaty proposed so far, with its status and the points it holds in escrow
+#[allow(dead_code)]
+#[utoipa::path(
+    post,
+    path = "/games/{id}/treaties",
+    params(("id" = String, Path, example = json!(String::from("woop")))),
+    responses((status = 200, body = TreatyList)),
+)]
+pub async fn list_treaties() {}
+
+/// Propose a treaty to another player, nothing changes hands until they accept it
+#[allow(dead_code)]
+#[utoipa::path(
+    post,
+    path = "/games/{id}/treaties/propose",
+    request_body(
+        content = Proposal,
+        example = json!(Proposal {
+            counterpart: String::from("fin"),
+            gives: vec![Term::Points { amount: 10 }],
+            asks: vec![
+                Term::PointsOnDay { amount: 10, day: 12 },
+                Term::NonAggression { until: 12 },
+            ],
+        }),
+    ),
+    params(
+        ("id" = String, Path, example = json!(String::from("woop"))),
+        ("username" = String, Header, example = json!(String::from("mirko.faina"))),
+        ("token" = String, Header, example = json!(String::from("this_is_a_token"))),
+    ),
+    responses(
+        (status = 200, body = Treaty),
+    ),
+)]
+pub async fn propose_treaty() {}
+
+/// Accept a treaty proposed to you. The points due now are handed over and the ones due later
+/// are held by the server, both sides need the points for it.
+#[allow(dead_code)]
+#[utoipa::path(
+    post,
+    path = "/games/{id}/treaties/{treaty}/accept",
+    params(
+        ("id" = String, Path, example = json!(String::from("woop"))),
+        ("treaty" = u64, Path, example = json!(0)),
+        ("username" = String, Header, example = json!(String::from("mirko.faina"))),
+        ("token" = String, Header, example = json!(String::from("this_is_a_token"))),
+    ),
+    responses(
+        (status = 200, body = Empty),
+    ),
+)]
+pub async fn accept_treaty() {}
+
+/// Decline a treaty proposed to you, or withdraw one you proposed, before it's accepted
+#[allow(dead_code)]
+#[utoipa::path(
+    post,
+    path = "/games/{id}/treaties/{treaty}/reject",
+    params(
+        ("id" = String, Path, example = json!(String::from("woop"))),
+        ("treaty" = u64, Path, example = json!(0)),
+        ("username" = String, Header, example = json!(String::from("mirko.faina"))),
+        ("token" = String, Header, example = json!(String::from("this_is_a_token"))),
+    ),
+    responses(
+        (status = 200, body = Empty),
+    ),
+)]
+pub async fn reject_treaty() {}
+
+/// Get your conversations, most recent first, with the messages you haven't read yet
+#[allow(dead_code)]
+#[utoipa::path(
+    post,
+    path = "/games/{id}/messages",
+    params(
+        ("id" = String, Path, example = json!(String::from("woop"))),
+        ("username" = String, Header, example = json!(String::from("mirko.faina"))),
+        ("token" = String, Header, example = json!(String::from("this_is_a_token"))),
+    ),
+    responses(
+        (status = 200, body = Inbox),
+    ),
+)]
+pub async fn inbox() {}
+
+/// Send a message to another player or to a group you are in
+#[allow(dead_code)]
+#[utoipa::path(
+    post,
+    path = "/games/{id}/messages/send",
+    request_body(
+        content = SendMessage,
+        example = json!(SendMessage {
+            channel: Channel::Direct(String::from("fin")),
+            body: String::from("Truce until the totems move?"),
+        }),
+    ),
+    params(
+        ("id" = String, Path, example = json!(String::from("woop"))),
+        ("username" = String, Header, example = json!(String::from("mirko.faina"))),
+        ("token" = String, Header, example = json!(String::from("this_is_a_token"))),
+    ),
+    responses(
+        (status = 200, body = Empty),
+    ),
+)]
+pub async fn send_message() {}
+
+/// Get the messages of a conversation, which are then marked as read
+#[allow(dead_code)]
+#[utoipa::path(
+    post,
+    path = "/games/{id}/messages/read",
+    request_body(
+        content = Channel,
+        example = json!(Channel::Direct(String::from("fin"))),
+    ),
+    params(
+        ("id" = String, Path, example = json!(String::from("woop"))),
+        ("username" = String, Header, example = json!(String::from("mirko.faina"))),
+        ("token" = String, Header, example = json!(String::from("this_is_a_token"))),
+    ),
+    responses(
+        (status = 200, body = MessageList),
+    ),
+)]
+pub async fn read_messages() {}
+
+/// Start a group and invite other players to it, nobody else can be invited afterwards
+#[allow(dead_code)]
+#[utoipa::path(
+    post,
+    path = "/games/{id}/messages/groups/new",
+    request_body(
+        content = NewGroup,
+        example = json!(NewGroup {
+            name: String::from("council"),
+            members: vec![String::from("fin"), String::from("warden")],
+        }),
+    ),
+    params(
+        ("id" = String, Path, example = json!(String::from("woop"))),
+        ("username" = String, Header, example = json!(String::from("mirko.faina"))),
+        ("token" = String, Header, example = json!(String::from("this_is_a_token"))),
+    ),
+    responses(
+        (status = 200, body = Empty),
+    ),
+)]
+pub async fn new_group() {}
+
+/// Join a group you were invited to
+#[allow(dead_code)]
+#[utoipa::path(
+    post,
+    path = "/games/{id}/messages/groups/join",
+    request_body(
+        content = GroupName,
+        example = json!(GroupName {group: String::from("council")}),
+    ),
+    params(
+        ("id" = String, Path, example = json!(String::from("woop"))),
+        ("username" = String, Header, example = json!(String::from("mirko.faina"))),
+        ("token" = String, Header, example = json!(String::from("this_is_a_token"))),
+    ),
+    responses(
+        (status = 200, body = Empty),
+    ),
+)]
+pub async fn join_group() {}
+
+/// Get every message sent during the game, once it ended and if the config reveals them
+#[allow(dead_code)]
+#[utoipa::path(
+    post,
+    path = "/games/{id}/messages/all",
+    params(("id" = String, Path, example = json!(String::from("woop")))),
+    responses((status = 200, body = MessageList)),
+)]
+pub async fn all_messages() {}
+
+/// Server-sent events with the game events you can see and your messages, as they happen. Each
+/// event is named `event` or `message`. Browsers can't set headers on an `EventSource`, they
+/// pass a ticket from `/stream/ticket` instead.
+#[allow(dead_code)]
+#[utoipa::path(
+    get,
+    path = "/games/{id}/stream",
+    params(
+        ("id" = String, Path, example = json!(String::from("woop"))),
+        StreamQuery,
+        (
+            "username" = Option<String>,
+            Header,
+            description = "Needed to get messages and to see the events when the game limits \
+                the vision",
+            example = json!(String::from("mirko.faina")),
+        ),
+        ("token" = Option<String>, Header, example = json!(String::from("this_is_a_token"))),
+    ),
+    responses((status = 200, content_type = "text/event-stream", body = String)),
+)]
+pub async fn stream() {}
+
+/// Get a ticket to open the stream with, it can be used once within a minute
+#[allow(dead_code)]
+#[utoipa::path(
+    post,
+    path = "/games/{id}/stream/ticket",
+    params(
+        ("id" = String, Path, example = json!(String::from("woop"))),
+        ("username" = String, Header, example = json!(String::from("mirko.faina"))),
+        ("token" = String, Header, example = json!(String::from("this_is_a_token"))),
+    ),
+    responses((status = 200, body = StreamTicket)),
+)]
+pub async fn stream_ticket() {}
+
+/// Get the statistics of every player over the whole game
+#[allow(dead_code)]
+#[utoipa::path(
+    post,
+    path = "/games/{id}/stats",
+    params(("id" = String, Path, example = json!(String::from("woop")))),
+    responses((status = 200, body = GameStats)),
+)]
+pub async fn get_stats() {}
+
+/// Get the statistics of a player, over the whole game and for each day
+#[allow(dead_code)]
+#[utoipa::path(
+    post,
+    path = "/games/{id}/stats/{player}",
+    params(
+        ("id" = String, Path, example = json!(String::from("woop"))),
+        ("player" = String, Path, example = json!(String::from("mirko.faina"))),
+    ),
+    responses(
+        (status = 200, body = PlayerRecord),
+        (status = 404, body = Empty),
+    ),
+)]
+pub async fn get_player_stats() {}
+
+/// Get who shot and who funded whom, along with the betrayals
+#[allow(dead_code)]
+#[utoipa::path(
+    post,
+    path = "/games/{id}/graph",
+    params(("id" = String, Path, example = json!(String::from("woop"))), GraphQuery),
+    responses((status = 200, content(
+        (InteractionGraph = "application/json"),
+        (String = "text/vnd.graphviz"),
+    ))),
+)]
+pub async fn get_graph() {}
+
 /// Get info on the current day
 #[allow(dead_code)]
-#[utoipa::path(post, path = "/day", responses((status = 200, body = GameInfo),))]
+#[utoipa::path(
+    post,
+    path = "/games/{id}/day",
+    params(("id" = String, Path, example = json!(String::from("woop")))),
+    responses((status = 200, body = GameInfo)),
+)]
 pub async fn get_day() {}
 
-/// Get a list of the last 100 actions
+/// Get a page of actions, newest first, optionally filtered
 #[allow(dead_code)]
-#[utoipa::path(post, path = "/activity", responses((status = 200, body = Activity),))]
+#[utoipa::path(
+    post,
+    path = "/games/{id}/activity",
+    params(
+        ("id" = String, Path, example = json!(String::from("woop"))),
+        (
+            "username" = Option<String>,
+            Header,
+            description = "Needed to see the events when the game limits the vision",
+            example = json!(String::from("mirko.faina")),
+        ),
+        ("token" = Option<String>, Header, example = json!(String::from("this_is_a_token"))),
+        EventQuery,
+    ),
+    responses((status = 200, body = Activity)),
+)]
 pub async fn get_activity() {}
 
+/// Get the whole board, as it was `spectator_delay` minutes ago
+#[allow(dead_code)]
+#[utoipa::path(
+    post,
+    path = "/games/{id}/spectate/board",
+    params(
+        ("id" = String, Path, example = json!(String::from("woop"))),
+        ("username" = String, Header, example = json!(String::from("commentator"))),
+        ("token" = String, Header, example = json!(String::from("this_is_a_token"))),
+    ),
+    responses(
+        (status = 200, body = PastBoard),
+    ),
+)]
+pub async fn spectate_board() {}
+
+/// Get a page of the actions older than `spectator_delay` minutes, newest first
+#[allow(dead_code)]
+#[utoipa::path(
+    post,
+    path = "/games/{id}/spectate/activity",
+    params(
+        ("id" = String, Path, example = json!(String::from("woop"))),
+        ("username" = String, Header, example = json!(String::from("commentator"))),
+        ("token" = String, Header, example = json!(String::from("this_is_a_token"))),
+        EventQuery,
+    ),
+    responses((status = 200, body = Activity)),
+)]
+pub async fn spectate_activity() {}
+
 /// Endpoint to check credentails
 #[allow(dead_code)]
 #[utoipa::path(
     post,
-    path = "/auth",
+    path = "/games/{id}/auth",
     params(
+        ("id" = String, Path, example = json!(String::from("woop"))),
         ("username" = String, Header, example = json!(String::from("mirko.faina"))),
         ("token" = String, Header, example = json!(String::from("this_is_a_token"))),
     ),
@@ -183,3 +860,8 @@ pub async fn get_activity() {}
     ),
 )]
 pub async fn authenticate() {}
+
+/// Get the list of games hosted by the server
+#[allow(dead_code)]
+#[utoipa::path(post, path = "/games", responses((status = 200, body = GameList),))]
+pub async fn list_games() {}
@@ -1,5 +1,21 @@
-use crate::game::{log::PlayerEvent, player::Player, totem::Totem, zord::Zord};
+use crate::game::{
+    action::Action,
+    game::Phase,
+    graph::{Betrayal, Edge},
+    log::PlayerEvent,
+    messages::{Channel, Message},
+    orders::{PlayedOrder, QueuedOrder},
+    player::Player,
+    stats::PlayerStats,
+    tactics::{ActionCosts, ZordTactics},
+    team::Team,
+    totem::Totem,
+    treaty::{Term, Treaty},
+    zord::Zord,
+};
+use crate::health::Persistence;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use utoipa::{IntoParams, ToSchema};
 
 #[derive(Deserialize, Serialize, Clone, ToSchema, IntoParams)]
@@ -21,6 +37,85 @@ pub struct Donate {
     pub amount: u16,
 }
 
+#[derive(Deserialize, Serialize, Clone, ToSchema)]
+pub struct TeamName {
+    pub team: String,
+}
+
+#[derive(Deserialize, Serialize, Clone, ToSchema)]
+pub struct Invite {
+    /// Player invited to join your team
+    pub player: String,
+}
+
+#[derive(Deserialize, Serialize, Clone, ToSchema)]
+pub struct Proposal {
+    /// Player the treaty is proposed to
+    pub counterpart: String,
+    /// What you commit to
+    #[serde(default)]
+    pub gives: Vec<Term>,
+    /// What the counterpart commits to
+    #[serde(default)]
+    pub asks: Vec<Term>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct TreatyList<'a> {
+    /// Oldest first
+    pub treaties: Vec<&'a Treaty>,
+}
+
+#[derive(Deserialize, Serialize, Clone, ToSchema)]
+pub struct SendMessage {
+    pub channel: Channel,
+    pub body: String,
+}
+
+#[derive(Deserialize, Serialize, Clone, ToSchema)]
+pub struct NewGroup {
+    pub name: String,
+    /// Players invited to join the group
+    pub members: Vec<String>,
+}
+
+#[derive(Deserialize, Serialize, Clone, ToSchema)]
+pub struct GroupName {
+    pub group: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct MessageList<'a> {
+    /// Oldest first
+    pub messages: &'a [Message],
+}
+
+#[derive(Deserialize, Serialize, Clone, ToSchema)]
+pub struct Batch {
+    /// Actions to play, in order
+    pub actions: Vec<Action>,
+    /// Play the actions up to the first one that fails, instead of none of them
+    #[serde(default)]
+    pub best_effort: bool,
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Step {
+    Ok,
+    Error(String),
+    /// Not tried because an earlier action failed
+    Skipped,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct BatchResult {
+    /// Number of actions that were played
+    pub applied: usize,
+    /// Result of every action, in the order they were sent
+    pub steps: Vec<Step>,
+}
+
 #[derive(Serialize, ToSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum Empty {
@@ -28,9 +123,18 @@ pub enum Empty {
     Ok,
 }
 
+#[derive(Serialize, ToSchema)]
+pub struct OrderList<'a> {
+    /// Orders waiting to be played, oldest first
+    pub pending: Vec<&'a QueuedOrder>,
+    /// Orders played since the start of the day
+    pub played: Vec<&'a PlayedOrder>,
+}
+
 #[derive(Serialize, ToSchema)]
 pub struct WoopMap<'a> {
-    pub zords: &'a Vec<Zord>,
+    /// Zords the caller can see, all of them unless the game limits the vision
+    pub zords: Vec<&'a Zord>,
     pub totems: (&'a Totem, &'a Totem),
 }
 
@@ -40,6 +144,35 @@ pub struct Leaderboard<'a> {
     pub leaderboard: &'a Vec<&'a Player>,
 }
 
+#[derive(Serialize, ToSchema)]
+pub struct TeamList<'a> {
+    pub teams: Vec<Team<'a>>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct TeamScore<'a> {
+    pub name: &'a str,
+    pub members: Vec<&'a str>,
+    /// Sum of the points of the members
+    pub points: u32,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct TeamLeaderboard<'a> {
+    /// List of teams sorted by points
+    pub leaderboard: Vec<TeamScore<'a>>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct Me<'a> {
+    pub player: &'a Player,
+    /// Your zords with the enemies around them
+    pub zords: Vec<ZordTactics<'a>>,
+    /// Shooting isn't allowed until the grace period is over
+    pub within_grace_period: bool,
+    pub costs: ActionCosts,
+}
+
 #[derive(Serialize, ToSchema)]
 pub struct GameInfo {
     /// Current game day
@@ -50,6 +183,87 @@ pub struct GameInfo {
 
 #[derive(Serialize, ToSchema)]
 pub struct Activity {
-    /// List of the last 100 actions
+    /// Page of actions matching the query, newest first
     pub activity: Vec<PlayerEvent>,
+    /// Pass as `before` to get the previous page, missing when the page is empty
+    pub before: Option<u64>,
+    /// Pass as `after` to get the next page, missing when the page is empty
+    pub after: Option<u64>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct GameStats {
+    /// Counters of every player over the whole game
+    pub players: BTreeMap<String, PlayerStats>,
+}
+
+#[derive(Deserialize, Default, Clone, Copy, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum GraphFormat {
+    #[default]
+    Json,
+    /// Graphviz DOT language
+    Dot,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct StreamQuery {
+    /// Ticket from `/stream/ticket`, in place of the `username` and `token` headers
+    pub ticket: Option<String>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct GraphQuery {
+    /// `json` (the default) or `dot`
+    #[serde(default)]
+    pub format: GraphFormat,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct InteractionGraph<'a> {
+    pub players: Vec<&'a String>,
+    /// One edge for every pair of players that interacted, in that direction
+    pub edges: Vec<&'a Edge>,
+    /// Shots at a player the shooter previously donated to or received points from
+    pub betrayals: &'a Vec<Betrayal>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct GameSummary {
+    /// Identifier to use in the `/games/{id}` routes
+    pub id: String,
+    /// Current game day
+    pub day: u8,
+    /// Number of players taking part in the game
+    pub players: usize,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct GameList {
+    /// Games hosted by this server
+    pub games: Vec<GameSummary>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct GameHealth {
+    pub id: String,
+    pub phase: Phase,
+    /// Whether this game is fit to be served
+    pub ready: bool,
+    /// Whether the thread advancing the game days is running
+    pub scheduler_alive: bool,
+    /// Unix timestamp of the last time the scheduler checked for pending jobs
+    pub last_tick: Option<u64>,
+    /// Unix timestamp of the last time the game advanced to the next day
+    pub last_rollover: Option<u64>,
+    pub persistence: Persistence,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct Readiness {
+    /// Whether every game is ready
+    pub ready: bool,
+    pub games: Vec<GameHealth>,
 }
@@ -1,3 +1,5 @@
+#[allow(clippy::module_inception)]
 pub mod api;
+mod assets;
 pub mod docs;
 mod message;
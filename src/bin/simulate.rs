@@ -0,0 +1,349 @@
+//! Plays whole games between bots at CPU speed and reports how they played out, to
+//! have some evidence before touching the rules.
+
+use clap::{Parser, ValueEnum};
+use rand::{seq::SliceRandom, Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use std::{
+    collections::BTreeMap,
+    time::{Duration, UNIX_EPOCH},
+};
+use woop_attack::{
+    config::Config,
+    game::{
+        action::Action,
+        bot::{play_turn, Bot, BotStrategy, GameView, Hunter},
+        clock::Clock,
+        game::{Game, ACTION_COST, GRACE_PERIOD, LAST_DAY},
+    },
+};
+
+const DAY: Duration = Duration::from_secs(60 * 60 * 24);
+
+#[derive(Parser)]
+struct Args {
+    /// Games to play, each one with its own seed
+    #[arg(short, long, default_value_t = 100)]
+    games: u64,
+    /// Players taking part in each game
+    #[arg(short, long, default_value_t = 8)]
+    players: usize,
+    /// Seed of the first game, the following games use the next seeds
+    #[arg(short, long, default_value_t = 0)]
+    seed: u64,
+    /// Strategies handed out to the players in turn
+    #[arg(
+        long,
+        value_enum,
+        value_delimiter = ',',
+        default_values_t = [Strategy::Hunter, Strategy::Camper, Strategy::Wanderer]
+    )]
+    strategies: Vec<Strategy>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+enum Strategy {
+    /// Chases and shoots the closest enemy zord
+    Hunter,
+    /// Parks next to the closest totem, shields and builds around it
+    Camper,
+    /// Stays put behind its shields and only shoots what comes in range
+    Turtle,
+    /// Does something random
+    Wanderer,
+}
+
+impl Strategy {
+    fn bot(self, rng: &mut ChaCha8Rng) -> Box<dyn Bot> {
+        match self {
+            Self::Hunter => BotStrategy::Hunter.bot(),
+            Self::Camper => BotStrategy::TotemCamper.bot(),
+            Self::Turtle => BotStrategy::Turtle.bot(),
+            Self::Wanderer => Box::new(Wanderer {
+                rng: ChaCha8Rng::seed_from_u64(rng.random()),
+            }),
+        }
+    }
+}
+
+struct Wanderer {
+    rng: ChaCha8Rng,
+}
+
+impl Bot for Wanderer {
+    fn next_action(&mut self, view: &GameView, player: &str) -> Option<Action> {
+        if view.actions(player) < ACTION_COST {
+            return None;
+        }
+        let zords: Vec<(i16, i16)> = view.own_zords(player).map(|z| (z.x, z.y)).collect();
+        let &(x, y) = zords.get(self.rng.random_range(0..zords.len().max(1)))?;
+        match self.rng.random_range(0..4) {
+            0 => Some(Action::Shield { coord: (x, y) }),
+            1 => Some(Action::IncreaseRange { coord: (x, y) }),
+            2 => Hunter.next_action(view, player),
+            _ => {
+                let to = (
+                    x + self.rng.random_range(-3..=3),
+                    y + self.rng.random_range(-3..=3),
+                );
+                Some(Action::Move { from: (x, y), to })
+            }
+        }
+    }
+}
+
+struct PlayerReport {
+    strategy: Strategy,
+    points: u16,
+    kills: u64,
+    totem_income: u64,
+}
+
+struct GameReport {
+    players: BTreeMap<String, PlayerReport>,
+    /// Leaders at the end of day 7 and 14
+    early_leaders: [String; 2],
+}
+
+impl GameReport {
+    fn winner(&self) -> &str {
+        leader(self.players.iter().map(|(name, p)| (name, p.points)))
+    }
+
+    fn totem_holder(&self) -> &str {
+        leader(self.players.iter().map(|(name, p)| (name, p.totem_income)))
+    }
+}
+
+// Ties go to the first player by name, so that reports don't depend on anything but the seed
+fn leader<'a, T: Ord>(players: impl Iterator<Item = (&'a String, T)>) -> &'a str {
+    players
+        .fold(
+            None,
+            |best: Option<(&String, T)>, (name, value)| match best {
+                Some((_, ref best_value)) if *best_value >= value => best,
+                _ => Some((name, value)),
+            },
+        )
+        .map(|(name, _)| name.as_str())
+        .unwrap_or_default()
+}
+
+fn play_game(seed: u64, strategies: &BTreeMap<String, Strategy>) -> GameReport {
+    let players: Vec<&str> = strategies.keys().map(String::as_str).collect();
+    let config = Config {
+        start_of_game: UNIX_EPOCH,
+        seed: Some(seed),
+        ..Config::with_players(format!("simulation-{}", seed).as_str(), &players)
+    };
+    let clock = Clock::manual(UNIX_EPOCH);
+    let mut game = Game::new(&config);
+    game.set_clock(clock.clone());
+    // Separate from the rng of the game, so the players don't change how the game rolls
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    let mut bots: BTreeMap<&String, Box<dyn Bot>> = strategies
+        .iter()
+        .map(|(name, strategy)| (name, strategy.bot(&mut rng)))
+        .collect();
+    let mut early_leaders = Vec::new();
+
+    for day in 1..=LAST_DAY {
+        game.new_day();
+        let grace = Duration::from_secs(GRACE_PERIOD + 1);
+        clock.advance(grace);
+
+        let mut order: Vec<&String> = strategies.keys().collect();
+        order.shuffle(&mut rng);
+        for player in order {
+            play_turn(&mut game, player, bots.get_mut(player).unwrap().as_mut());
+        }
+        if day == 7 || day == 14 {
+            let points: BTreeMap<&String, u16> = game
+                .players
+                .iter()
+                .map(|(name, p)| (name, p.points))
+                .collect();
+            early_leaders.push(leader(points.into_iter()).to_string());
+        }
+        clock.advance(DAY - grace);
+    }
+    // The last rollover hands out the totem points of the last day
+    game.new_day();
+
+    let stats = game.logged_actions.stats();
+    let players = strategies
+        .iter()
+        .map(|(name, strategy)| {
+            let total = stats
+                .player(name)
+                .map(|record| record.total.clone())
+                .unwrap_or_default();
+            let report = PlayerReport {
+                strategy: *strategy,
+                points: game.players[name].points,
+                kills: total.kills,
+                totem_income: total.totem_income,
+            };
+            (name.clone(), report)
+        })
+        .collect();
+    GameReport {
+        players,
+        early_leaders: early_leaders.try_into().unwrap(),
+    }
+}
+
+// Share of the points held by the players, 0 when everyone has the same score and 1 when a
+// single player has them all
+fn gini(points: &[u64]) -> f64 {
+    let total: u64 = points.iter().sum();
+    if total == 0 {
+        return 0.0;
+    }
+    let differences: u64 = points
+        .iter()
+        .flat_map(|a| points.iter().map(move |b| a.abs_diff(*b)))
+        .sum();
+    differences as f64 / (2.0 * points.len() as f64 * total as f64)
+}
+
+fn distribution(mut values: Vec<u64>) -> String {
+    if values.is_empty() {
+        return String::from("-");
+    }
+    values.sort();
+    let at = |q: f64| values[((values.len() - 1) as f64 * q).round() as usize];
+    let mean = values.iter().sum::<u64>() as f64 / values.len() as f64;
+    format!(
+        "min {:>4}  p10 {:>4}  median {:>4}  p90 {:>4}  max {:>4}  mean {:>7.1}",
+        at(0.0),
+        at(0.1),
+        at(0.5),
+        at(0.9),
+        at(1.0),
+        mean
+    )
+}
+
+fn percent(count: usize, total: usize) -> f64 {
+    100.0 * count as f64 / total.max(1) as f64
+}
+
+fn main() {
+    let args = Args::parse();
+    if args.strategies.is_empty() || args.players < 2 {
+        eprintln!("A game needs at least two players and one strategy");
+        std::process::exit(1);
+    }
+    let strategies: BTreeMap<String, Strategy> = (0..args.players)
+        .map(|i| {
+            let strategy = args.strategies[i % args.strategies.len()];
+            (format!("player-{:02}", i), strategy)
+        })
+        .collect();
+
+    let reports: Vec<GameReport> = (0..args.games)
+        .map(|i| play_game(args.seed + i, &strategies))
+        .collect();
+    let games = reports.len();
+    let players = || reports.iter().flat_map(|report| report.players.values());
+
+    println!(
+        "{} games, {} players each, seeds {}..={}\n",
+        games,
+        args.players,
+        args.seed,
+        args.seed + args.games.saturating_sub(1)
+    );
+    println!(
+        "Final score      {}",
+        distribution(players().map(|p| p.points as u64).collect())
+    );
+    println!(
+        "Winning score    {}",
+        distribution(
+            reports
+                .iter()
+                .map(|r| r.players[r.winner()].points as u64)
+                .collect()
+        )
+    );
+    println!(
+        "Kills            {}",
+        distribution(players().map(|p| p.kills).collect())
+    );
+    println!(
+        "Totem income     {}",
+        distribution(players().map(|p| p.totem_income).collect())
+    );
+
+    let ginis: Vec<f64> = reports
+        .iter()
+        .map(|r| {
+            gini(
+                &r.players
+                    .values()
+                    .map(|p| p.points as u64)
+                    .collect::<Vec<_>>(),
+            )
+        })
+        .collect();
+    println!("\nSnowballing");
+    println!(
+        "  Day 7 leader wins           {:>5.1}%",
+        percent(
+            reports
+                .iter()
+                .filter(|r| r.early_leaders[0] == r.winner())
+                .count(),
+            games
+        )
+    );
+    println!(
+        "  Day 14 leader wins          {:>5.1}%",
+        percent(
+            reports
+                .iter()
+                .filter(|r| r.early_leaders[1] == r.winner())
+                .count(),
+            games
+        )
+    );
+    println!(
+        "  Top totem earner wins       {:>5.1}%",
+        percent(
+            reports
+                .iter()
+                .filter(|r| r.totem_holder() == r.winner())
+                .count(),
+            games
+        )
+    );
+    println!(
+        "  Gini of final scores        {:>6.2}",
+        ginis.iter().sum::<f64>() / ginis.len().max(1) as f64
+    );
+
+    println!("\nBy strategy");
+    let mut by_strategy: BTreeMap<Strategy, (usize, usize, u64, u64)> = BTreeMap::new();
+    for report in reports.iter() {
+        let winner = report.winner();
+        for (name, player) in report.players.iter() {
+            let entry = by_strategy.entry(player.strategy).or_default();
+            entry.0 += 1;
+            entry.1 += (name == winner) as usize;
+            entry.2 += player.points as u64;
+            entry.3 += player.kills;
+        }
+    }
+    for (strategy, (count, wins, points, kills)) in by_strategy {
+        println!(
+            "  {:<10} wins {:>5.1}% (fair share {:>5.1}%)  mean score {:>7.1}  mean kills {:>5.1}",
+            format!("{:?}", strategy).to_lowercase(),
+            percent(wins, games),
+            percent(count, games * args.players),
+            points as f64 / count as f64,
+            kills as f64 / count as f64
+        );
+    }
+}
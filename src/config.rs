@@ -1,18 +1,89 @@
+use crate::game::{bot::BotStrategy, journal::FsyncPolicy, team::TeamRules};
+use chrono::NaiveTime;
 use serde::Deserialize;
 use std::{
     fs::read,
+    path::{Path, PathBuf},
     time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
+const DEFAULT_ROLLOVER: &str = "6:00 am";
+const DEFAULT_SPECTATOR_DELAY: u64 = 60;
+
 #[derive(Deserialize)]
 struct RawConfig {
+    id: Option<String>,
     players: String,
     start_of_game: u64,
+    rollover: Option<String>,
+    snapshot: Option<PathBuf>,
+    log_dir: Option<PathBuf>,
+    #[serde(default)]
+    log_fsync: FsyncPolicy,
+    seed: Option<u64>,
+    #[serde(default)]
+    bots: Vec<RawBot>,
+    vision: Option<u16>,
+    spectators: Option<String>,
+    /// Minutes
+    spectator_delay: Option<u64>,
+    #[serde(default)]
+    teams: Vec<TeamConfig>,
+    #[serde(default)]
+    team_rules: TeamRules,
+    #[serde(default)]
+    reveal_messages: bool,
 }
 
+#[derive(Deserialize)]
+struct RawBot {
+    name: String,
+    strategy: BotStrategy,
+    play_at: Vec<String>,
+}
+
+/// A player controlled by the server
+#[derive(Clone)]
+pub struct BotConfig {
+    pub name: String,
+    pub strategy: BotStrategy,
+    /// Times of the day at which the bot plays its turn
+    pub play_at: Vec<NaiveTime>,
+}
+
+/// A team the players start the game in
+#[derive(Clone, Deserialize)]
+pub struct TeamConfig {
+    pub name: String,
+    pub members: Vec<String>,
+}
+
+#[derive(Clone)]
 pub struct Config {
+    /// Identifier used in the `/games/{id}` routes, defaults to the name of the config file
+    pub id: String,
     pub players: Vec<String>,
     pub start_of_game: SystemTime,
+    /// Time of the day at which the game advances to the next day
+    pub rollover: NaiveTime,
+    /// Where the game state is saved, the game is restored from it when the server starts
+    pub snapshot: Option<PathBuf>,
+    /// Directory of the journal holding every logged event, one file per day
+    pub log_dir: Option<PathBuf>,
+    pub log_fsync: FsyncPolicy,
+    /// Seed of the rng placing totems and respawns, a random one is picked when missing
+    pub seed: Option<u64>,
+    pub bots: Vec<BotConfig>,
+    /// How far players see from their zords, everything is visible to everyone when missing
+    pub vision: Option<u16>,
+    /// Watch the game without playing, they see everything but only as it was `spectator_delay`
+    /// ago
+    pub spectators: Vec<String>,
+    pub spectator_delay: Duration,
+    pub teams: Vec<TeamConfig>,
+    pub team_rules: TeamRules,
+    /// Everyone can read every message once the game ended
+    pub reveal_messages: bool,
 }
 
 impl Config {
@@ -20,9 +91,115 @@ impl Config {
         let data = String::from_utf8(read(path).expect("Couldn't read config file"))
             .expect("Couldn't read config file");
         let config: RawConfig = toml::from_str(data.as_str()).unwrap();
+        let id = config.id.unwrap_or_else(|| {
+            Path::new(path)
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .expect("Couldn't derive game id from config file name")
+                .to_string()
+        });
+        let players: Vec<String> = config.players.split('|').map(String::from).collect();
+        let bots: Vec<BotConfig> = config
+            .bots
+            .into_iter()
+            .map(|bot| BotConfig {
+                play_at: bot
+                    .play_at
+                    .iter()
+                    .map(|time| parse_time(time).expect("Couldn't parse bot play time"))
+                    .collect(),
+                name: bot.name,
+                strategy: bot.strategy,
+            })
+            .collect();
+        if let Some(bot) = bots.iter().find(|bot| players.contains(&bot.name)) {
+            panic!("Bot {} has the same name as a player", bot.name);
+        }
+        let spectators: Vec<String> = config
+            .spectators
+            .iter()
+            .flat_map(|spectators| spectators.split('|').map(String::from))
+            .collect();
+        if let Some(spectator) = spectators
+            .iter()
+            .find(|name| players.contains(name) || bots.iter().any(|bot| &bot.name == *name))
+        {
+            panic!("Spectator {} has the same name as a player", spectator);
+        }
+        for (i, team) in config.teams.iter().enumerate() {
+            if config.teams[..i]
+                .iter()
+                .any(|other| other.name == team.name)
+            {
+                panic!("There's more than one team named {}", team.name);
+            }
+            if let Some(member) = team
+                .members
+                .iter()
+                .find(|name| !players.contains(name) && !bots.iter().any(|bot| &bot.name == *name))
+            {
+                panic!(
+                    "Team {} has {} as a member, who isn't playing",
+                    team.name, member
+                );
+            }
+            if let Some(member) = team.members.iter().find(|name| {
+                config.teams[..i]
+                    .iter()
+                    .any(|other| other.members.contains(name))
+            }) {
+                panic!("{} is in more than one team", member);
+            }
+        }
         Self {
-            players: config.players.split('|').map(String::from).collect(),
+            id,
+            players,
             start_of_game: UNIX_EPOCH + Duration::from_secs(config.start_of_game),
+            rollover: parse_time(config.rollover.as_deref().unwrap_or(DEFAULT_ROLLOVER))
+                .expect("Couldn't parse rollover time"),
+            snapshot: config.snapshot,
+            log_dir: config.log_dir,
+            log_fsync: config.log_fsync,
+            seed: config.seed,
+            bots,
+            vision: config.vision,
+            spectators,
+            spectator_delay: Duration::from_secs(
+                config.spectator_delay.unwrap_or(DEFAULT_SPECTATOR_DELAY) * 60,
+            ),
+            teams: config.teams,
+            team_rules: config.team_rules,
+            reveal_messages: config.reveal_messages,
+        }
+    }
+
+    /// A game between `players` starting now, with every optional setting left to its default.
+    /// Meant for games set up in code, like the simulations and the tests.
+    pub fn with_players(id: &str, players: &[&str]) -> Config {
+        Self {
+            id: id.to_string(),
+            players: players.iter().map(|player| player.to_string()).collect(),
+            start_of_game: SystemTime::now(),
+            rollover: parse_time(DEFAULT_ROLLOVER).unwrap(),
+            snapshot: None,
+            log_dir: None,
+            log_fsync: FsyncPolicy::default(),
+            seed: None,
+            bots: Vec::new(),
+            vision: None,
+            spectators: Vec::new(),
+            spectator_delay: Duration::from_secs(DEFAULT_SPECTATOR_DELAY * 60),
+            teams: Vec::new(),
+            team_rules: TeamRules::default(),
+            reveal_messages: false,
         }
     }
 }
+
+// Accepts the same formats as clokwerk
+fn parse_time(time: &str) -> Result<NaiveTime, chrono::ParseError> {
+    NaiveTime::parse_from_str(time, "%H:%M:%S")
+        .or_else(|_| NaiveTime::parse_from_str(time, "%I:%M:%S %p"))
+        .or_else(|_| NaiveTime::parse_from_str(time, "%H:%M"))
+        .or_else(|_| NaiveTime::parse_from_str(time, "%I:%M %p"))
+}
@@ -0,0 +1,324 @@
+use crate::game::{game::Game, log::PlayerEvent};
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::Path,
+    time::UNIX_EPOCH,
+};
+use zip::{write::SimpleFileOptions, ZipWriter};
+
+/// One row for every logged event, columns that don't apply to the event are left empty
+#[derive(Serialize)]
+struct EventRow<'a> {
+    id: u64,
+    day: u8,
+    timestamp: u64,
+    kind: &'static str,
+    player: Option<&'a str>,
+    target: Option<&'a str>,
+    x: Option<i16>,
+    y: Option<i16>,
+    to_x: Option<i16>,
+    to_y: Option<i16>,
+    /// Points earned, paid, donated or lost because of the event
+    points: Option<u16>,
+    shielded: Option<bool>,
+    hp_left: Option<u8>,
+    destroyed: Option<bool>,
+    wiped_out: Option<bool>,
+    shields: Option<u8>,
+    range: Option<u8>,
+}
+
+impl<'a> From<&'a PlayerEvent> for EventRow<'a> {
+    fn from(event: &'a PlayerEvent) -> Self {
+        let players = event.players();
+        let coords = event.coords();
+        let mut row = EventRow {
+            id: event.id(),
+            day: event.day(),
+            timestamp: event.timestamp(),
+            kind: event.kind(),
+            player: players.first().copied(),
+            target: players.get(1).copied(),
+            x: coords.first().map(|c| c.0),
+            y: coords.first().map(|c| c.1),
+            to_x: coords.get(1).map(|c| c.0),
+            to_y: coords.get(1).map(|c| c.1),
+            points: None,
+            shielded: None,
+            hp_left: None,
+            destroyed: None,
+            wiped_out: None,
+            shields: None,
+            range: None,
+        };
+        match event {
+            PlayerEvent::Shoot {
+                shielded,
+                hp_left,
+                destroyed,
+                reward,
+                wiped_out,
+                ..
+            } => {
+                row.points = Some(*reward);
+                row.shielded = Some(*shielded);
+                row.hp_left = Some(*hp_left);
+                row.destroyed = Some(*destroyed);
+                row.wiped_out = Some(*wiped_out);
+            }
+            PlayerEvent::DonatePoints { amount, .. } | PlayerEvent::TreatyPoints { amount, .. } => {
+                row.points = Some(*amount)
+            }
+            PlayerEvent::BuildZord { cost, .. } => row.points = Some(*cost),
+            PlayerEvent::TotemPoints { points, .. } | PlayerEvent::PointPenalty { points, .. } => {
+                row.points = Some(*points)
+            }
+            PlayerEvent::ShieldExpired { shields, .. } => row.shields = Some(*shields),
+            PlayerEvent::RangeReset { range, .. } => row.range = Some(*range),
+            _ => (),
+        }
+        row
+    }
+}
+
+#[derive(Serialize)]
+struct ScoreRow<'a> {
+    day: u8,
+    player: &'a str,
+    points: u16,
+    actions_left: u8,
+    zords: usize,
+}
+
+/// A zord from the moment it was built or respawned until it was destroyed
+#[derive(Serialize)]
+struct ZordLifetime {
+    owner: String,
+    /// `respawn` or `build_zord`
+    origin: &'static str,
+    born_day: u8,
+    born_event: u64,
+    died_day: Option<u8>,
+    died_event: Option<u64>,
+    killer: Option<String>,
+    moves: u64,
+    /// Last cell the zord was in
+    x: i16,
+    y: i16,
+}
+
+pub fn write_events<W: Write>(game: &Game, writer: W) -> io::Result<()> {
+    let mut writer = csv::Writer::from_writer(writer);
+    for event in game.logged_actions.oldest_first() {
+        writer.serialize(EventRow::from(&event))?;
+    }
+    writer.flush()
+}
+
+/// Points of every player at the end of each day, the current day included
+pub fn write_daily_scores<W: Write>(game: &Game, writer: W) -> io::Result<()> {
+    let mut writer = csv::Writer::from_writer(writer);
+    let mut current: Vec<_> = game.players.values().cloned().collect();
+    current.sort_by(|a, b| a.name.cmp(&b.name));
+    let days = game
+        .history
+        .iter()
+        .map(|state| (state.day, &state.players, &state.zords))
+        .chain([(game.day, &current, &game.zords)]);
+    for (day, players, zords) in days {
+        for player in players.iter() {
+            writer.serialize(ScoreRow {
+                day,
+                player: player.name.as_str(),
+                points: player.points,
+                actions_left: player.actions,
+                zords: zords.iter().filter(|z| z.owner == player.name).count(),
+            })?;
+        }
+    }
+    writer.flush()
+}
+
+// Zords have no identity of their own, they are followed through the log by their position
+fn zord_lifetimes(game: &Game) -> Vec<ZordLifetime> {
+    let mut lifetimes: Vec<ZordLifetime> = Vec::new();
+    let mut alive: HashMap<(i16, i16), usize> = HashMap::new();
+    for event in game.logged_actions.oldest_first() {
+        match &event {
+            PlayerEvent::Respawn { player, .. } | PlayerEvent::BuildZord { player, .. } => {
+                let (x, y) = event.coords()[0];
+                alive.insert((x, y), lifetimes.len());
+                lifetimes.push(ZordLifetime {
+                    owner: player.clone(),
+                    origin: event.kind(),
+                    born_day: event.day(),
+                    born_event: event.id(),
+                    died_day: None,
+                    died_event: None,
+                    killer: None,
+                    moves: 0,
+                    x,
+                    y,
+                });
+            }
+            PlayerEvent::Move { from, to, .. } => {
+                if let Some(index) = alive.remove(from) {
+                    lifetimes[index].moves += 1;
+                    (lifetimes[index].x, lifetimes[index].y) = *to;
+                    alive.insert(*to, index);
+                }
+            }
+            PlayerEvent::Shoot {
+                id,
+                day,
+                shooter,
+                to,
+                destroyed: true,
+                ..
+            } => {
+                if let Some(index) = alive.remove(to) {
+                    lifetimes[index].died_day = Some(*day);
+                    lifetimes[index].died_event = Some(*id);
+                    lifetimes[index].killer = Some(shooter.clone());
+                }
+            }
+            _ => (),
+        }
+    }
+    lifetimes
+}
+
+pub fn write_zord_lifetimes<W: Write>(game: &Game, writer: W) -> io::Result<()> {
+    let mut writer = csv::Writer::from_writer(writer);
+    for lifetime in zord_lifetimes(game) {
+        writer.serialize(lifetime)?;
+    }
+    writer.flush()
+}
+
+/// Writes `events.csv`, `daily_scores.csv` and `zord_lifetimes.csv` in `dir`
+pub fn export_csv(game: &Game, dir: &Path) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    write_events(game, BufWriter::new(File::create(dir.join("events.csv"))?))?;
+    write_daily_scores(game, File::create(dir.join("daily_scores.csv"))?)?;
+    write_zord_lifetimes(game, File::create(dir.join("zord_lifetimes.csv"))?)
+}
+
+#[derive(Serialize)]
+struct Manifest<'a> {
+    id: &'a str,
+    day: u8,
+    seed: u64,
+    events: u64,
+    exported_at: u64,
+}
+
+// State of the game as it's shared in an archive, without the tokens of the players and the
+// spectators, the stored replies and, unless the API would reveal them, the private messages
+fn redacted_state(game: &Game) -> serde_json::Result<serde_json::Value> {
+    let mut state = serde_json::to_value(game)?;
+    if let Some(fields) = state.as_object_mut() {
+        fields.remove("auth");
+        fields.remove("spectators");
+        fields.remove("replies");
+        if game.revealed_messages().is_err() {
+            fields.remove("messages");
+        }
+    }
+    Ok(state)
+}
+
+/// Bundles everything there is to know about a game in a zip archive: the config it was started
+/// with, its final state without secrets, the full activity log and the CSV tables
+pub fn write_archive(game: &Game, config: &Path, path: &Path) -> io::Result<()> {
+    let mut zip = ZipWriter::new(BufWriter::new(File::create(path)?));
+    let options = SimpleFileOptions::default();
+
+    zip.start_file("manifest.json", options)?;
+    serde_json::to_writer_pretty(
+        &mut zip,
+        &Manifest {
+            id: game.id.as_str(),
+            day: game.day,
+            seed: game.seed,
+            events: game.logged_actions.counts().values().sum(),
+            exported_at: UNIX_EPOCH.elapsed().unwrap().as_secs(),
+        },
+    )?;
+    zip.start_file("config.toml", options)?;
+    zip.write_all(fs::read(config)?.as_slice())?;
+    zip.start_file("state.json", options)?;
+    serde_json::to_writer(&mut zip, &redacted_state(game)?)?;
+    zip.start_file("events.jsonl", options)?;
+    for event in game.logged_actions.oldest_first() {
+        serde_json::to_writer(&mut zip, &event)?;
+        zip.write_all(b"\n")?;
+    }
+    zip.start_file("events.csv", options)?;
+    write_events(game, &mut zip)?;
+    zip.start_file("daily_scores.csv", options)?;
+    write_daily_scores(game, &mut zip)?;
+    zip.start_file("zord_lifetimes.csv", options)?;
+    write_zord_lifetimes(game, &mut zip)?;
+    zip.finish()?.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{redacted_state, zord_lifetimes};
+    use crate::{
+        config::Config,
+        game::{
+            game::{Game, LAST_DAY},
+            log::ShotOutcome,
+        },
+    };
+
+    #[test]
+    fn follow_zords_through_the_log() {
+        let config = Config::with_players("test", &["mroik", "fin"]);
+        let mut game = Game::new(&config);
+        let log = &mut game.logged_actions;
+        log.respawn("mroik", (0, 0));
+        log.respawn("fin", (3, 3));
+        log.move_zord("fin", (3, 3), (2, 2));
+        log.shoot(
+            "mroik",
+            (0, 0),
+            (2, 2),
+            "fin",
+            ShotOutcome {
+                shielded: false,
+                hp_left: 0,
+                destroyed: true,
+                reward: 3,
+                wiped_out: true,
+            },
+        );
+        log.build_zord("fin", (2, 2), 10);
+
+        let lifetimes = zord_lifetimes(&game);
+        assert_eq!(lifetimes.len(), 3);
+        assert_eq!(lifetimes[0].died_event, None);
+        assert_eq!(lifetimes[1].moves, 1);
+        assert_eq!(lifetimes[1].died_event, Some(3));
+        assert_eq!(lifetimes[1].killer.as_deref(), Some("mroik"));
+        assert_eq!(lifetimes[2].origin, "build_zord");
+        assert_eq!((lifetimes[2].x, lifetimes[2].y), (2, 2));
+
+        let state = redacted_state(&game).unwrap();
+        for secret in ["auth", "spectators", "replies", "messages"] {
+            assert!(state.get(secret).is_none(), "{} is in the archive", secret);
+        }
+        assert!(state.get("players").is_some());
+        game.reveal_messages = true;
+        game.day = 1;
+        assert!(redacted_state(&game).unwrap().get("messages").is_none());
+        game.day = LAST_DAY + 1;
+        assert!(redacted_state(&game).unwrap().get("messages").is_some());
+    }
+}
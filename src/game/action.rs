@@ -0,0 +1,31 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Anything a player can do on their turn, mirrors the action routes of the api
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case", tag = "action")]
+pub enum Action {
+    Shoot {
+        /// Coordinates of your own zord
+        from: (i16, i16),
+        /// Coordinates of target zord
+        to: (i16, i16),
+    },
+    Move {
+        from: (i16, i16),
+        to: (i16, i16),
+    },
+    Shield {
+        coord: (i16, i16),
+    },
+    IncreaseRange {
+        coord: (i16, i16),
+    },
+    DonatePoints {
+        receiver: String,
+        amount: u16,
+    },
+    BuildZord {
+        coord: (i16, i16),
+    },
+}
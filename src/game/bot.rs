@@ -0,0 +1,319 @@
+use super::{
+    action::Action,
+    game::{Game, ACTION_COST, BASE_BOARD_SIZE, NEW_ZORD_COST, TOTEM_AURA},
+    player::Player,
+    zord::{distance, Zord},
+};
+use serde::Deserialize;
+
+// A bot's turn is over after this many of its actions have been rejected
+const MAX_REJECTED: u8 = 5;
+// Shields the turtle keeps on each of its zords
+const TURTLE_SHIELDS: u8 = 3;
+
+/// What a bot gets to see of the game, it can't change anything through it
+pub struct GameView<'a> {
+    game: &'a Game,
+}
+
+impl<'a> GameView<'a> {
+    pub fn new(game: &'a Game) -> Self {
+        Self { game }
+    }
+
+    pub fn day(&self) -> u8 {
+        self.game.day
+    }
+
+    pub fn player(&self, name: &str) -> Option<&'a Player> {
+        self.game.players.get(name)
+    }
+
+    pub fn players(&self) -> impl Iterator<Item = &'a Player> {
+        self.game.players.values()
+    }
+
+    pub fn zords(&self) -> &'a [Zord] {
+        self.game.zords.as_slice()
+    }
+
+    pub fn own_zords<'b>(&self, player: &'b str) -> impl Iterator<Item = &'a Zord> + 'b
+    where
+        'a: 'b,
+    {
+        self.game
+            .zords
+            .iter()
+            .filter(move |zord| zord.owner == player)
+    }
+
+    /// The zord belongs to someone who is neither `player` nor one of their allies
+    pub fn is_enemy(&self, player: &str, zord: &Zord) -> bool {
+        zord.owner != player && !self.game.teams.allies(player, zord.owner.as_str())
+    }
+
+    pub fn totems(&self) -> [(i16, i16); 2] {
+        let (a, b) = &self.game.totems;
+        [(a.x, a.y), (b.x, b.y)]
+    }
+
+    /// Actions `player` has left today
+    pub fn actions(&self, player: &str) -> u8 {
+        self.player(player).map_or(0, |player| player.actions)
+    }
+
+    /// The cell is on the board and there's no zord in it
+    pub fn is_free(&self, (x, y): (i16, i16)) -> bool {
+        let on_board = (0..BASE_BOARD_SIZE).contains(&x) && (0..BASE_BOARD_SIZE).contains(&y);
+        on_board
+            && !self
+                .game
+                .zords
+                .iter()
+                .any(|zord| (zord.x, zord.y) == (x, y))
+    }
+}
+
+/// A player controlled by the server. A bot picks one action at a time, which is then carried out
+/// with the same checks a human player goes through.
+pub trait Bot {
+    /// Next action of `player`, `None` ends the turn
+    fn next_action(&mut self, view: &GameView, player: &str) -> Option<Action>;
+}
+
+/// Lets `bot` play as `player` until it has nothing left to do or keeps being rejected, returns
+/// how many actions went through
+pub fn play_turn(game: &mut Game, player: &str, bot: &mut dyn Bot) -> usize {
+    let mut played = 0;
+    let mut rejected = 0;
+    while rejected < MAX_REJECTED {
+        let Some(action) = bot.next_action(&GameView::new(game), player) else {
+            break;
+        };
+        match game.apply(player, &action) {
+            Ok(()) => played += 1,
+            Err(err) => {
+                log::debug!("Bot {} couldn't {:?}: {}", player, action, err);
+                rejected += 1;
+            }
+        }
+    }
+    played
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BotStrategy {
+    /// Parks next to the closest totem, shields and builds around it
+    TotemCamper,
+    /// Chases and shoots the closest enemy zord
+    Hunter,
+    /// Stays put behind its shields and only shoots what comes in range
+    Turtle,
+}
+
+impl BotStrategy {
+    pub fn bot(self) -> Box<dyn Bot> {
+        match self {
+            Self::TotemCamper => Box::new(TotemCamper),
+            Self::Hunter => Box::new(Hunter),
+            Self::Turtle => Box::new(Turtle),
+        }
+    }
+}
+
+// The cell `steps` cells away from `from` on the way to `to`
+fn step_toward(from: (i16, i16), to: (i16, i16), steps: i16) -> (i16, i16) {
+    let step = |a: i16, b: i16| a + (b - a).signum() * steps.min((b - a).abs());
+    (step(from.0, to.0), step(from.1, to.1))
+}
+
+// Moves up to `steps` cells toward `to`, stopping short when the cell is taken
+fn approach(
+    view: &GameView,
+    player: &str,
+    from: (i16, i16),
+    to: (i16, i16),
+    steps: i16,
+) -> Option<Action> {
+    let steps = steps.min(view.actions(player) as i16);
+    (1..=steps)
+        .rev()
+        .map(|steps| step_toward(from, to, steps))
+        .find(|cell| view.is_free(*cell))
+        .map(|to| Action::Move { from, to })
+}
+
+// Closest enemy zord within range of an own zord
+fn enemy_in_range(view: &GameView, player: &str) -> Option<Action> {
+    view.own_zords(player)
+        .flat_map(|own| {
+            view.zords()
+                .iter()
+                .filter(|enemy| view.is_enemy(player, enemy))
+                .map(move |enemy| ((own.x, own.y), (enemy.x, enemy.y), own.range))
+        })
+        .filter(|(own, enemy, range)| distance(*own, *enemy) <= *range as i16)
+        .min_by_key(|(own, enemy, _)| distance(*own, *enemy))
+        .map(|(from, to, _)| Action::Shoot { from, to })
+}
+
+// A free cell next to `zord`, the closest to `target`
+fn free_neighbour(view: &GameView, zord: (i16, i16), target: (i16, i16)) -> Option<(i16, i16)> {
+    (-1..=1)
+        .flat_map(|dx| (-1..=1).map(move |dy| (zord.0 + dx, zord.1 + dy)))
+        .filter(|cell| view.is_free(*cell))
+        .min_by_key(|cell| distance(*cell, target))
+}
+
+fn can_build(view: &GameView, player: &str) -> bool {
+    view.actions(player) >= ACTION_COST
+        && view
+            .player(player)
+            .is_some_and(|player| player.points >= NEW_ZORD_COST)
+}
+
+pub struct Hunter;
+
+impl Bot for Hunter {
+    fn next_action(&mut self, view: &GameView, player: &str) -> Option<Action> {
+        if view.actions(player) >= ACTION_COST {
+            if let Some(shot) = enemy_in_range(view, player) {
+                return Some(shot);
+            }
+        }
+        let (own, enemy) = view
+            .own_zords(player)
+            .flat_map(|own| {
+                view.zords()
+                    .iter()
+                    .filter(|enemy| view.is_enemy(player, enemy))
+                    .map(move |enemy| (own, enemy))
+            })
+            .min_by_key(|(own, enemy)| distance((own.x, own.y), (enemy.x, enemy.y)))?;
+        let (from, to) = ((own.x, own.y), (enemy.x, enemy.y));
+        approach(
+            view,
+            player,
+            from,
+            to,
+            distance(from, to) - own.range as i16,
+        )
+    }
+}
+
+pub struct TotemCamper;
+
+impl Bot for TotemCamper {
+    fn next_action(&mut self, view: &GameView, player: &str) -> Option<Action> {
+        let (own, totem) = view
+            .own_zords(player)
+            .flat_map(|zord| view.totems().map(|totem| ((zord.x, zord.y), totem)))
+            .min_by_key(|(own, totem)| distance(*own, *totem))?;
+        let gap = distance(own, totem) - TOTEM_AURA as i16;
+        if gap > 0 {
+            return approach(view, player, own, totem, gap);
+        }
+
+        if can_build(view, player) {
+            let cell = free_neighbour(view, own, totem)
+                .filter(|cell| distance(*cell, totem) <= TOTEM_AURA as i16);
+            if let Some(coord) = cell {
+                return Some(Action::BuildZord { coord });
+            }
+        }
+        if view.actions(player) < ACTION_COST {
+            return None;
+        }
+        let unshielded = view.own_zords(player).find(|zord| {
+            zord.shields < 2 && distance((zord.x, zord.y), totem) <= TOTEM_AURA as i16
+        });
+        match unshielded {
+            Some(zord) => Some(Action::Shield {
+                coord: (zord.x, zord.y),
+            }),
+            None => enemy_in_range(view, player),
+        }
+    }
+}
+
+pub struct Turtle;
+
+impl Bot for Turtle {
+    fn next_action(&mut self, view: &GameView, player: &str) -> Option<Action> {
+        if view.actions(player) < ACTION_COST {
+            return None;
+        }
+        if let Some(zord) = view
+            .own_zords(player)
+            .find(|zord| zord.shields < TURTLE_SHIELDS)
+        {
+            return Some(Action::Shield {
+                coord: (zord.x, zord.y),
+            });
+        }
+        if can_build(view, player) {
+            let cell = view
+                .own_zords(player)
+                .find_map(|zord| free_neighbour(view, (zord.x, zord.y), (zord.x, zord.y)));
+            if let Some(coord) = cell {
+                return Some(Action::BuildZord { coord });
+            }
+        }
+        enemy_in_range(view, player)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{play_turn, Action, BotStrategy, GameView};
+    use crate::{
+        config::Config,
+        game::{
+            game::{Game, GRACE_PERIOD},
+            player::BASE_ACTIONS,
+            zord::Zord,
+        },
+    };
+    use std::time::Duration;
+
+    fn generate_game() -> Game {
+        let config = Config::with_players("test", &["mroik", "fin"]);
+        let mut game = Game::new(&config);
+        game.start_of_day = game
+            .start_of_day
+            .checked_sub(Duration::from_secs(GRACE_PERIOD + 1))
+            .unwrap();
+        game
+    }
+
+    #[test]
+    fn hunter_closes_in_and_shoots() {
+        let mut game = generate_game();
+        game.zords.push(Zord::new("mroik", 0, 0));
+        game.zords.push(Zord::new("fin", 10, 0));
+        let mut hunter = BotStrategy::Hunter.bot();
+
+        let first = hunter.next_action(&GameView::new(&game), "mroik");
+        assert_eq!(
+            first,
+            Some(Action::Move {
+                from: (0, 0),
+                to: (5, 0)
+            })
+        );
+        let played = play_turn(&mut game, "mroik", hunter.as_mut());
+        assert_eq!(played, 3);
+        assert!(game.zords.iter().all(|zord| zord.owner == "mroik"));
+        assert_eq!(game.players["mroik"].actions, BASE_ACTIONS - 5 - 8);
+    }
+
+    #[test]
+    fn turtle_shields_up() {
+        let mut game = generate_game();
+        game.zords.push(Zord::new("fin", 0, 0));
+        play_turn(&mut game, "fin", BotStrategy::Turtle.bot().as_mut());
+        assert_eq!(game.zords[0].shields, 3);
+        assert_eq!(game.players["fin"].actions, BASE_ACTIONS - 12);
+    }
+}
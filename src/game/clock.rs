@@ -0,0 +1,61 @@
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Where a game reads the current time from. The server follows the system clock, while
+/// simulations move a manual clock forward themselves to play a whole game at CPU speed.
+#[derive(Debug, Clone, Default)]
+pub enum Clock {
+    #[default]
+    System,
+    /// Milliseconds since the unix epoch, shared by every clone of the clock
+    Manual(Arc<AtomicU64>),
+}
+
+impl Clock {
+    pub fn manual(start: SystemTime) -> Self {
+        let millis = start.duration_since(UNIX_EPOCH).unwrap().as_millis() as u64;
+        Self::Manual(Arc::new(AtomicU64::new(millis)))
+    }
+
+    pub fn now(&self) -> SystemTime {
+        match self {
+            Self::System => SystemTime::now(),
+            Self::Manual(millis) => {
+                UNIX_EPOCH + Duration::from_millis(millis.load(Ordering::SeqCst))
+            }
+        }
+    }
+
+    /// Moves a manual clock forward, the system clock can't be moved
+    pub fn advance(&self, by: Duration) {
+        match self {
+            Self::System => panic!("The system clock can't be advanced"),
+            Self::Manual(millis) => {
+                millis.fetch_add(by.as_millis() as u64, Ordering::SeqCst);
+            }
+        }
+    }
+
+    pub fn unix_timestamp(&self) -> u64 {
+        self.now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Clock;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn advance_manual_clock() {
+        let clock = Clock::manual(UNIX_EPOCH + Duration::from_secs(100));
+        let shared = clock.clone();
+        clock.advance(Duration::from_secs(60));
+        assert_eq!(shared.unix_timestamp(), 160);
+    }
+}
@@ -1,3 +1,10 @@
+use super::{
+    game::MAX_BATCH_SIZE,
+    messages::{MAX_GROUPS, MAX_MESSAGES_PER_DAY, MAX_MESSAGE_LENGTH},
+    orders::MAX_ORDERS,
+    replies::MAX_KEYS,
+    treaty::MAX_PROPOSALS,
+};
 use serde::Serialize;
 use std::{
     error::Error,
@@ -20,9 +27,77 @@ pub enum WoopError {
     DonationLimit,
     DonationRange,
     OwnZord,
+    GameNotFound(String),
+    ShuttingDown,
+    ChunkRemoved,
+    BatchTooLong,
+    TooManyOrders,
+    OrderNotFound(u64),
+    KeyReused(String),
+    TooManyKeys,
+    AlreadyInTeam(String),
+    TeamNameTaken(String),
+    NotInTeam,
+    NotInvited(String),
+    FriendlyFire,
+    MessageTooLong,
+    TooManyMessages,
+    GroupNameTaken(String),
+    GroupNotFound(String),
+    NotInvitedToGroup(String),
+    TooManyGroups,
+    MessagesHidden,
+    TreatyNotFound(u64),
+    InvalidTreaty(&'static str),
+    TreatyUnaffordable(String),
+    TooManyProposals,
 }
 
 impl WoopError {
+    /// Name of the variant, used to label metrics
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::OutOfActions => "out_of_actions",
+            Self::ZordNotFound(..) => "zord_not_found",
+            Self::NotEnoughPoints(..) => "not_enough_points",
+            Self::CellOccupied(..) => "cell_occupied",
+            Self::OutOfBounds(..) => "out_of_bounds",
+            Self::NotInRange(..) => "not_in_range",
+            Self::PlayerNotFound(_) => "player_not_found",
+            Self::WithinGracePeriod => "within_grace_period",
+            Self::NoZordNearby(..) => "no_zord_nearby",
+            Self::NotOwned(..) => "not_owned",
+            Self::AuthError => "auth_error",
+            Self::DonationLimit => "donation_limit",
+            Self::DonationRange => "donation_range",
+            Self::OwnZord => "own_zord",
+            Self::GameNotFound(_) => "game_not_found",
+            Self::ShuttingDown => "shutting_down",
+            Self::ChunkRemoved => "chunk_removed",
+            Self::BatchTooLong => "batch_too_long",
+            Self::TooManyOrders => "too_many_orders",
+            Self::OrderNotFound(_) => "order_not_found",
+            Self::KeyReused(_) => "key_reused",
+            Self::TooManyKeys => "too_many_keys",
+            Self::AlreadyInTeam(_) => "already_in_team",
+            Self::TeamNameTaken(_) => "team_name_taken",
+            Self::NotInTeam => "not_in_team",
+            Self::NotInvited(_) => "not_invited",
+            Self::FriendlyFire => "friendly_fire",
+            Self::MessageTooLong => "message_too_long",
+            Self::TooManyMessages => "too_many_messages",
+            Self::GroupNameTaken(_) => "group_name_taken",
+            Self::GroupNotFound(_) => "group_not_found",
+            Self::NotInvitedToGroup(_) => "not_invited_to_group",
+            Self::TooManyGroups => "too_many_groups",
+            Self::MessagesHidden => "messages_hidden",
+            Self::TreatyNotFound(_) => "treaty_not_found",
+            Self::InvalidTreaty(_) => "invalid_treaty",
+            Self::TreatyUnaffordable(_) => "treaty_unaffordable",
+            Self::TooManyProposals => "too_many_proposals",
+        }
+    }
+
     pub fn out_of_actions() -> Result<(), WoopError> {
         Err(WoopError::OutOfActions)
     }
@@ -101,6 +176,65 @@ impl Display for WoopError {
             Self::DonationLimit => write!(f, "You can donate at most 10 points with each action"),
             Self::DonationRange => write!(f, "Out of range for a donation"),
             Self::OwnZord => write!(f, "You can't shoot your own zord"),
+            Self::GameNotFound(id) => write!(f, "Couldn't find game named {}", id),
+            Self::ShuttingDown => write!(f, "The server is shutting down"),
+            Self::ChunkRemoved => write!(
+                f,
+                "chunk is no longer supported, page through the activity with before and after"
+            ),
+            Self::BatchTooLong => {
+                write!(f, "A batch can't hold more than {} actions", MAX_BATCH_SIZE)
+            }
+            Self::TooManyOrders => {
+                write!(f, "You can't have more than {} orders waiting", MAX_ORDERS)
+            }
+            Self::OrderNotFound(id) => write!(f, "Couldn't find order {}", id),
+            Self::KeyReused(key) => {
+                write!(
+                    f,
+                    "Idempotency key {} was already used for another request",
+                    key
+                )
+            }
+            Self::TooManyKeys => write!(
+                f,
+                "You can't use more than {} idempotency keys in a day",
+                MAX_KEYS
+            ),
+            Self::AlreadyInTeam(team) => write!(f, "Already in team {}", team),
+            Self::TeamNameTaken(team) => write!(f, "There's already a team named {}", team),
+            Self::NotInTeam => write!(f, "You're not in a team"),
+            Self::NotInvited(team) => write!(f, "You weren't invited to team {}", team),
+            Self::FriendlyFire => write!(f, "You can't shoot the zords of your allies"),
+            Self::MessageTooLong => write!(
+                f,
+                "Messages can be at most {} characters long",
+                MAX_MESSAGE_LENGTH
+            ),
+            Self::TooManyMessages => write!(
+                f,
+                "You can't send more than {} messages in a day",
+                MAX_MESSAGES_PER_DAY
+            ),
+            Self::GroupNameTaken(group) => write!(f, "There's already a group named {}", group),
+            Self::GroupNotFound(group) => write!(f, "You're not in a group named {}", group),
+            Self::NotInvitedToGroup(group) => {
+                write!(f, "You weren't invited to group {}", group)
+            }
+            Self::TooManyGroups => {
+                write!(f, "You can't be in more than {} groups", MAX_GROUPS)
+            }
+            Self::MessagesHidden => write!(f, "Messages are only revealed once the game ended"),
+            Self::TreatyNotFound(id) => write!(f, "Couldn't find treaty {}", id),
+            Self::InvalidTreaty(reason) => write!(f, "Invalid treaty: {}", reason),
+            Self::TreatyUnaffordable(player) => {
+                write!(f, "{} doesn't have the points the treaty asks for", player)
+            }
+            Self::TooManyProposals => write!(
+                f,
+                "You can't have more than {} treaties waiting for an answer",
+                MAX_PROPOSALS
+            ),
         }
     }
 }
@@ -0,0 +1,50 @@
+use super::{log::PlayerEvent, messages::Message};
+use serde::Serialize;
+use tokio::sync::broadcast::{self, Receiver, Sender};
+
+// Items a subscriber can fall behind by before missing some
+const FEED_CAPACITY: usize = 256;
+
+/// Something that just happened in the game
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Live {
+    Event(PlayerEvent),
+    Message(Message),
+}
+
+impl Live {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Event(_) => "event",
+            Self::Message(_) => "message",
+        }
+    }
+}
+
+/// Hands out what happens in the game to whoever is listening as it happens
+#[derive(Debug, Clone)]
+pub struct Feed {
+    sender: Sender<Live>,
+}
+
+impl Default for Feed {
+    fn default() -> Self {
+        Self {
+            sender: broadcast::channel(FEED_CAPACITY).0,
+        }
+    }
+}
+
+impl Feed {
+    pub fn subscribe(&self) -> Receiver<Live> {
+        self.sender.subscribe()
+    }
+
+    pub fn publish(&self, live: impl FnOnce() -> Live) {
+        // Nothing to do while nobody is listening
+        if self.sender.receiver_count() > 0 {
+            let _ = self.sender.send(live());
+        }
+    }
+}
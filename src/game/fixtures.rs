@@ -0,0 +1,62 @@
+// Events for the tests of the modules reading the activity log
+use super::log::{PlayerEvent, ShotOutcome};
+
+/// A shot leaving the target zord with 1 hp
+pub fn hit() -> ShotOutcome {
+    ShotOutcome {
+        shielded: false,
+        hp_left: 1,
+        destroyed: false,
+        reward: 0,
+        wiped_out: false,
+    }
+}
+
+pub fn shoot(id: u64, day: u8, shooter: &str, target: &str, outcome: ShotOutcome) -> PlayerEvent {
+    PlayerEvent::Shoot {
+        id,
+        day,
+        shooter: shooter.to_string(),
+        from: (0, 0),
+        to: (1, 1),
+        target: target.to_string(),
+        shielded: outcome.shielded,
+        hp_left: outcome.hp_left,
+        destroyed: outcome.destroyed,
+        reward: outcome.reward,
+        wiped_out: outcome.wiped_out,
+        timestamp: 0,
+    }
+}
+
+pub fn donate(id: u64, from: &str, to: &str, amount: u16) -> PlayerEvent {
+    PlayerEvent::DonatePoints {
+        id,
+        day: 1,
+        from: from.to_string(),
+        to: to.to_string(),
+        amount,
+        timestamp: 0,
+    }
+}
+
+pub fn move_zord(id: u64, player: &str, from: (i16, i16), to: (i16, i16)) -> PlayerEvent {
+    PlayerEvent::Move {
+        id,
+        day: 1,
+        player: player.to_string(),
+        from,
+        to,
+        timestamp: 0,
+    }
+}
+
+pub fn shield(id: u64, player: &str, zord_coord: (i16, i16)) -> PlayerEvent {
+    PlayerEvent::GenerateShield {
+        id,
+        day: 1,
+        player: player.to_string(),
+        zord_coord,
+        timestamp: 0,
+    }
+}
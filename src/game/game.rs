@@ -1,63 +1,257 @@
 use super::{
+    action::Action,
+    clock::Clock,
     error::WoopError,
-    log::Logger,
+    feed::Live,
+    log::{Logger, PlayerEvent, ShotOutcome},
+    messages::{Channel, Mailbox, Message},
+    orders::Orders,
     player::{Player, BASE_ACTIONS},
+    replies::Replies,
+    team::{TeamRules, Teams},
     totem::Totem,
-    zord::{Zord, BASE_RANGE},
+    treaty::{self, Escrow, Term, Treaties, Treaty, TreatyStatus, MAX_PROPOSALS},
+    vision::Sight,
+    zord::{distance, Zord, BASE_RANGE},
 };
 use crate::config::Config;
 use base64::{engine::general_purpose::URL_SAFE, Engine};
-use rand::{rng, Rng};
+use chrono::{Local, NaiveTime, TimeDelta};
+use rand::{rng, Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
-use std::{collections::HashMap, time::SystemTime};
-
-const BASE_BOARD_SIZE: i16 = 140;
-const GRACE_PERIOD: u64 = 60 * 60 * 3;
-const NEW_ZORD_COST: u16 = 10;
-const KILL_REWARD: u16 = 3;
-const TOTEM_AURA: u16 = 5;
-const TOTEM_REWARD: u16 = 50;
-const ACTION_COST: u8 = 4;
-const MAX_DONATION_PER_ACTION: u16 = 10;
-
-#[derive(Debug)]
+use std::{
+    collections::{BTreeMap, HashMap},
+    time::{Duration, SystemTime},
+};
+use utoipa::ToSchema;
+
+pub const BASE_BOARD_SIZE: i16 = 140;
+pub const GRACE_PERIOD: u64 = 60 * 60 * 3;
+pub const NEW_ZORD_COST: u16 = 10;
+pub const KILL_REWARD: u16 = 3;
+pub const TOTEM_AURA: u16 = 5;
+pub const TOTEM_REWARD: u16 = 50;
+pub const ACTION_COST: u8 = 4;
+pub const MAX_DONATION_PER_ACTION: u16 = 10;
+/// The game ends at the rollover out of this day
+pub const LAST_DAY: u8 = 28;
+/// The cheapest action costs a single action point, so no longer batch can go through
+pub const MAX_BATCH_SIZE: usize = BASE_ACTIONS as usize;
+/// Seconds a stream ticket can be used for
+pub const STREAM_TICKET_TTL: u64 = 60;
+
+#[derive(Debug, Serialize, PartialEq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Phase {
+    NotStarted,
+    Running,
+    Ended,
+}
+
+/// Players and zords as they were at the end of a day
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DayState {
+    pub day: u8,
+    pub players: Vec<Player>,
+    pub zords: Vec<Zord>,
+    /// Missing from the states saved before they were recorded
+    #[serde(default)]
+    pub totems: Option<(Totem, Totem)>,
+    /// Unix timestamp of the rollover that ended the day
+    #[serde(default)]
+    pub ended_at: u64,
+}
+
+/// Opens the live stream once in place of the `username` and `token` headers, which browsers
+/// can't set on an `EventSource`
+#[derive(Debug, Serialize, ToSchema)]
+pub struct StreamTicket {
+    pub ticket: String,
+    /// Unix timestamp after which the ticket is refused
+    pub expires_at: u64,
+}
+
+/// What an action would do, worked out on a copy of the game
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Preview {
+    /// Action points the action costs
+    pub cost: u8,
+    /// Action points left after the action
+    pub actions_left: u8,
+    /// Points left after the action
+    pub points_left: u16,
+    /// Events the action would log, a shot tells whether a shield would take the hit, the hp
+    /// left to the target and the reward for destroying it
+    pub events: Vec<PlayerEvent>,
+}
+
+fn new_token() -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(rng().random::<[u8; 32]>());
+    let data = hasher.finalize().to_vec();
+    let mut password = URL_SAFE.encode(data);
+    password.truncate(100);
+    password
+}
+
+// The id, the rollover time, the vision radius, the spectator delay, the team rules and whether
+// messages are revealed are owned by the config, so they are not part of the snapshots
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Game {
+    #[serde(skip)]
+    pub id: String,
+    #[serde(skip)]
+    pub rollover: NaiveTime,
+    #[serde(skip)]
+    pub vision: Option<u16>,
+    #[serde(skip)]
+    pub spectator_delay: Duration,
+    #[serde(skip)]
+    pub team_rules: TeamRules,
+    #[serde(skip)]
+    pub reveal_messages: bool,
+    #[serde(skip)]
+    clock: Clock,
+    // Player and expiry of the stream tickets handed out, keyed by ticket
+    #[serde(skip)]
+    stream_tickets: HashMap<String, (String, u64)>,
     pub players: HashMap<String, Player>,
     pub zords: Vec<Zord>,
     pub totems: (Totem, Totem),
     pub start_of_day: SystemTime,
     pub day: u8,
     pub auth: HashMap<String, String>,
+    /// Tokens of the spectators
+    #[serde(default)]
+    pub spectators: HashMap<String, String>,
     pub logged_actions: Logger,
+    /// State at the end of every day played so far
+    #[serde(default)]
+    pub history: Vec<DayState>,
+    #[serde(default)]
+    pub teams: Teams,
+    #[serde(default)]
+    pub messages: Mailbox,
+    #[serde(default)]
+    pub treaties: Treaties,
+    #[serde(default)]
+    pub orders: Orders,
+    /// Replies to today's actions sent with an idempotency key
+    #[serde(default)]
+    pub replies: Replies,
+    /// Seed of `rng`, replaying the log of a game with the same seed gives the same game
+    pub seed: u64,
+    // Every random choice of the game goes through this rng, which is saved along with the game
+    // so that a restored game keeps making the same choices
+    rng: ChaCha8Rng,
 }
 
 impl Game {
     pub fn new(config: &Config) -> Self {
-        let players: HashMap<String, Player> = config
+        let mut players: HashMap<String, Player> = config
             .players
             .iter()
             .map(|name| (name.clone(), Player::new(name)))
             .collect();
 
-        let mut rng = rng();
-        let mut auth = HashMap::new();
-        players.iter().for_each(|(_, p)| {
-            let mut hasher = Sha256::new();
-            hasher.update(rng.random::<[u8; 32]>());
-            let data = hasher.finalize().to_vec();
-            let mut password = URL_SAFE.encode(data);
-            password.truncate(100);
-            auth.insert(p.name.clone(), password);
+        // Bots have no token, nobody can play in their place
+        let auth = players
+            .keys()
+            .map(|name| (name.clone(), new_token()))
+            .collect();
+        config.bots.iter().for_each(|bot| {
+            players.insert(bot.name.clone(), Player::bot(bot.name.as_str()));
         });
 
-        Game {
+        let seed = config.seed.unwrap_or_else(rand::random);
+
+        let mut logged_actions = Logger::new();
+        if let Some(dir) = config.log_dir.as_deref() {
+            logged_actions
+                .attach_journal(dir, config.log_fsync)
+                .expect("Couldn't open the activity log");
+        }
+
+        let mut game = Game {
+            id: config.id.clone(),
+            rollover: config.rollover,
+            vision: config.vision,
+            spectator_delay: config.spectator_delay,
+            team_rules: config.team_rules,
+            reveal_messages: config.reveal_messages,
+            clock: Clock::default(),
+            stream_tickets: HashMap::new(),
             players,
             zords: Vec::new(),
             totems: (Totem::new(0, 0), Totem::new(0, 0)),
             start_of_day: SystemTime::now(),
             day: 0,
             auth,
-            logged_actions: Logger::new(),
+            spectators: HashMap::new(),
+            logged_actions,
+            history: Vec::new(),
+            teams: Teams::default(),
+            messages: Mailbox::default(),
+            treaties: Treaties::default(),
+            orders: Orders::default(),
+            replies: Replies::default(),
+            seed,
+            rng: ChaCha8Rng::seed_from_u64(seed),
+        };
+        game.set_spectators(&config.spectators);
+
+        // Teams of the config are there from the start, they aren't logged
+        for team in config.teams.iter() {
+            let Some((first, others)) = team.members.split_first() else {
+                continue;
+            };
+            game.teams.create(team.name.as_str(), first).unwrap();
+            for member in others {
+                game.teams.invite(first, member).unwrap();
+                game.teams.accept(member, team.name.as_str()).unwrap();
+            }
+        }
+        game
+    }
+
+    /// Hands out tokens to the spectators that don't have one yet and revokes the tokens of the
+    /// ones that are no longer in `names`
+    pub fn set_spectators(&mut self, names: &[String]) {
+        self.spectators.retain(|name, _| names.contains(name));
+        for name in names {
+            self.spectators
+                .entry(name.clone())
+                .or_insert_with(new_token);
+        }
+    }
+
+    /// Makes the game and its log follow `clock` instead of the system clock
+    pub fn set_clock(&mut self, clock: Clock) {
+        self.logged_actions.set_clock(clock.clone());
+        self.clock = clock;
+    }
+
+    pub fn phase(&self) -> Phase {
+        match self.day {
+            0 => Phase::NotStarted,
+            day if day > LAST_DAY => Phase::Ended,
+            _ => Phase::Running,
+        }
+    }
+
+    /// When the scheduler is going to advance the game to the next day
+    pub fn next_rollover(&self) -> SystemTime {
+        let now = Local::now();
+        let mut next = now.date_naive().and_time(self.rollover);
+        if next <= now.naive_local() {
+            next += TimeDelta::days(1);
+        }
+        match next.and_local_timezone(Local).earliest() {
+            Some(next) => next.into(),
+            // The rollover falls in a DST gap, an hour later is close enough
+            None => (now + TimeDelta::hours(1)).into(),
         }
     }
 
@@ -68,6 +262,407 @@ impl Game {
         }
     }
 
+    pub fn authenticate_spectator(&self, username: &str, pass: &str) -> Result<(), WoopError> {
+        match self.spectators.get(username) {
+            Some(p) if p.as_str() == pass => Ok(()),
+            _ => Err(WoopError::AuthError),
+        }
+    }
+
+    /// Hands out a stream ticket to `player`, dropping the expired ones
+    pub fn stream_ticket(&mut self, player: &str) -> StreamTicket {
+        let now = self.clock.unix_timestamp();
+        self.stream_tickets
+            .retain(|_, (_, expires_at)| *expires_at > now);
+        let ticket = StreamTicket {
+            ticket: new_token(),
+            expires_at: now + STREAM_TICKET_TTL,
+        };
+        self.stream_tickets.insert(
+            ticket.ticket.clone(),
+            (player.to_string(), ticket.expires_at),
+        );
+        ticket
+    }
+
+    /// The player a stream ticket was handed to, a ticket can only be used once
+    pub fn redeem_stream_ticket(&mut self, ticket: &str) -> Result<String, WoopError> {
+        match self.stream_tickets.remove(ticket) {
+            Some((player, expires_at)) if expires_at > self.clock.unix_timestamp() => Ok(player),
+            _ => Err(WoopError::AuthError),
+        }
+    }
+
+    /// Unix timestamp of the moment spectators are shown
+    pub fn spectator_time(&self) -> u64 {
+        self.clock
+            .unix_timestamp()
+            .saturating_sub(self.spectator_delay.as_secs())
+    }
+
+    /// Runs `action` for `player` through the same checks as the dedicated methods
+    pub fn apply(&mut self, player: &str, action: &Action) -> Result<(), WoopError> {
+        match action {
+            Action::Shoot { from, to } => self.player_shoot(player, from.0, from.1, to.0, to.1),
+            Action::Move { from, to } => self.move_zord(player, from.0, from.1, to.0, to.1),
+            Action::Shield { coord } => self.generate_shield(player, coord.0, coord.1),
+            Action::IncreaseRange { coord } => self.increase_range(player, coord.0, coord.1),
+            Action::DonatePoints { receiver, amount } => {
+                self.donate_points(player, receiver.as_str(), *amount)
+            }
+            Action::BuildZord { coord } => self.build_zord(player, coord.0, coord.1),
+        }
+    }
+
+    /// Plays `actions` in order. They are all tried on a copy of the game first and none of them
+    /// is played unless every one goes through, while with `best_effort` they are played until
+    /// the first one that fails. Returns the result of every action that was tried.
+    pub fn apply_batch(
+        &mut self,
+        player: &str,
+        actions: &[Action],
+        best_effort: bool,
+    ) -> Vec<Result<(), WoopError>> {
+        if !best_effort {
+            let results = self.scratch().apply_until_error(player, actions);
+            if results.iter().any(|result| result.is_err()) {
+                return results;
+            }
+        }
+        self.apply_until_error(player, actions)
+    }
+
+    fn apply_until_error(
+        &mut self,
+        player: &str,
+        actions: &[Action],
+    ) -> Vec<Result<(), WoopError>> {
+        let mut results = Vec::new();
+        for action in actions {
+            let result = self.apply(player, action);
+            let failed = result.is_err();
+            results.push(result);
+            if failed {
+                break;
+            }
+        }
+        results
+    }
+
+    // Copy of the game to try actions on. It logs to a detached logger and has no tokens, history
+    // or messages, none of which an action needs.
+    fn scratch(&self) -> Game {
+        Game {
+            id: self.id.clone(),
+            rollover: self.rollover,
+            vision: self.vision,
+            spectator_delay: self.spectator_delay,
+            team_rules: self.team_rules,
+            reveal_messages: self.reveal_messages,
+            clock: self.clock.clone(),
+            stream_tickets: HashMap::new(),
+            players: self.players.clone(),
+            zords: self.zords.clone(),
+            totems: self.totems.clone(),
+            start_of_day: self.start_of_day,
+            day: self.day,
+            auth: HashMap::new(),
+            spectators: HashMap::new(),
+            logged_actions: self.logged_actions.detached(),
+            history: Vec::new(),
+            teams: self.teams.clone(),
+            messages: Mailbox::default(),
+            treaties: self.treaties.clone(),
+            orders: Orders::default(),
+            replies: Replies::default(),
+            seed: self.seed,
+            rng: self.rng.clone(),
+        }
+    }
+
+    /// Runs `action` through the same checks as `apply` without changing the game, and tells
+    /// what it would cost and do
+    pub fn preview(&self, player: &str, action: &Action) -> Result<Preview, WoopError> {
+        let Some(before) = self.players.get(player) else {
+            return Err(WoopError::PlayerNotFound(String::from(player)));
+        };
+        let mut scratch = self.scratch();
+        scratch.apply(player, action)?;
+        let after = &scratch.players[player];
+        Ok(Preview {
+            cost: before.actions - after.actions,
+            actions_left: after.actions,
+            points_left: after.points,
+            events: scratch.logged_actions.oldest_first().collect(),
+        })
+    }
+
+    /// Some order is waiting to be played by `run_orders`
+    pub fn orders_due(&self) -> bool {
+        self.orders.any_due(
+            &self.zords,
+            &self.teams,
+            self.clock.unix_timestamp(),
+            self.within_grace_period(),
+        )
+    }
+
+    /// Plays the orders that are due through `apply`, returns how many were played
+    pub fn run_orders(&mut self) -> usize {
+        let now = self.clock.unix_timestamp();
+        let due = self
+            .orders
+            .take_due(&self.zords, &self.teams, now, self.within_grace_period());
+        let count = due.len();
+        for (order, action) in due {
+            let result = action.and_then(|action| self.apply(order.player.as_str(), &action));
+            let error = result.err().map(|err| err.to_string());
+            self.orders.played_order(order, now, error);
+        }
+        count
+    }
+
+    /// Shooting isn't allowed yet
+    pub fn within_grace_period(&self) -> bool {
+        let delta_t = self.clock.now().duration_since(self.start_of_day);
+        delta_t.unwrap_or_default().as_secs() <= GRACE_PERIOD
+    }
+
+    pub fn create_team(&mut self, player: &str, team: &str) -> Result<(), WoopError> {
+        if !self.players.contains_key(player) {
+            return WoopError::player_not_found(player);
+        }
+        self.teams.create(team, player)?;
+        self.logged_actions.team_created(team, player);
+        Ok(())
+    }
+
+    pub fn invite_to_team(&mut self, from: &str, to: &str) -> Result<(), WoopError> {
+        if !self.players.contains_key(to) {
+            return WoopError::player_not_found(to);
+        }
+        let team = self.teams.invite(from, to)?;
+        self.logged_actions.team_invite(team.as_str(), from, to);
+        Ok(())
+    }
+
+    pub fn join_team(&mut self, player: &str, team: &str) -> Result<(), WoopError> {
+        self.teams.accept(player, team)?;
+        self.logged_actions.team_joined(team, player);
+        Ok(())
+    }
+
+    pub fn leave_team(&mut self, player: &str) -> Result<(), WoopError> {
+        let team = self.teams.leave(player)?;
+        self.logged_actions.team_left(team.as_str(), player);
+        Ok(())
+    }
+
+    pub fn send_message(
+        &mut self,
+        from: &str,
+        channel: Channel,
+        body: String,
+    ) -> Result<(), WoopError> {
+        if let Channel::Direct(to) = &channel {
+            if !self.players.contains_key(to) {
+                return WoopError::player_not_found(to);
+            }
+        }
+        let now = self.clock.unix_timestamp();
+        let message = self.messages.send(from, channel, body, self.day, now)?;
+        self.logged_actions
+            .feed()
+            .publish(|| Live::Message(message.clone()));
+        Ok(())
+    }
+
+    pub fn create_group(
+        &mut self,
+        creator: &str,
+        name: &str,
+        members: &[String],
+    ) -> Result<(), WoopError> {
+        if let Some(member) = members.iter().find(|m| !self.players.contains_key(*m)) {
+            return WoopError::player_not_found(member);
+        }
+        self.messages.create_group(name, creator, members)
+    }
+
+    /// Every message sent during the game, once it ended and if the config reveals them
+    pub fn revealed_messages(&self) -> Result<&[Message], WoopError> {
+        match self.reveal_messages && self.phase() == Phase::Ended {
+            true => Ok(self.messages.all()),
+            false => Err(WoopError::MessagesHidden),
+        }
+    }
+
+    pub fn propose_treaty(
+        &mut self,
+        proposer: &str,
+        counterpart: &str,
+        gives: Vec<Term>,
+        asks: Vec<Term>,
+    ) -> Result<&Treaty, WoopError> {
+        if !self.players.contains_key(counterpart) {
+            return Err(WoopError::PlayerNotFound(counterpart.to_string()));
+        }
+        if proposer == counterpart {
+            return Err(WoopError::InvalidTreaty(
+                "You can't sign a treaty with yourself",
+            ));
+        }
+        if gives.is_empty() && asks.is_empty() {
+            return Err(WoopError::InvalidTreaty("A treaty needs at least one term"));
+        }
+        let pending = self
+            .treaties
+            .iter()
+            .filter(|treaty| treaty.proposer == proposer && treaty.status == TreatyStatus::Proposed)
+            .count();
+        if pending >= MAX_PROPOSALS {
+            return Err(WoopError::TooManyProposals);
+        }
+        treaty::validate(&gives, self.day)?;
+        treaty::validate(&asks, self.day)?;
+        let treaty = self.treaties.propose(proposer, counterpart, gives, asks);
+        self.logged_actions.treaty_update(treaty);
+        Ok(treaty)
+    }
+
+    /// Hands over the points due now and escrows the ones due later, both sides need the points
+    /// when the counterpart accepts
+    pub fn accept_treaty(&mut self, player: &str, id: u64) -> Result<(), WoopError> {
+        let treaty = match self.treaties.get_mut(id) {
+            Some(treaty)
+                if treaty.counterpart == player && treaty.status == TreatyStatus::Proposed =>
+            {
+                treaty
+            }
+            _ => return Err(WoopError::TreatyNotFound(id)),
+        };
+        // Days may have gone by since the proposal
+        treaty::validate(&treaty.gives, self.day)?;
+        treaty::validate(&treaty.asks, self.day)?;
+        for (party, _, _) in treaty.sides() {
+            if (self.players[party].points as u32) < treaty.cost(party) {
+                return Err(WoopError::TreatyUnaffordable(party.to_string()));
+            }
+        }
+
+        let mut escrow = Vec::new();
+        for (party, other, terms) in treaty.sides() {
+            for term in terms {
+                match term {
+                    Term::Points { amount } => {
+                        self.players.get_mut(party).unwrap().points -= *amount;
+                        self.players.get_mut(other).unwrap().points += *amount;
+                        self.logged_actions
+                            .treaty_points(id, Some(party), Some(other), *amount);
+                    }
+                    Term::PointsOnDay { amount, day } => {
+                        self.players.get_mut(party).unwrap().points -= *amount;
+                        self.logged_actions
+                            .treaty_points(id, Some(party), None, *amount);
+                        escrow.push(Escrow {
+                            from: party.to_string(),
+                            to: other.to_string(),
+                            amount: *amount,
+                            day: *day,
+                        });
+                    }
+                    Term::NonAggression { .. } => (),
+                }
+            }
+        }
+        treaty.escrow = escrow;
+        treaty.status = TreatyStatus::Active;
+        self.logged_actions.treaty_update(treaty);
+        if treaty.done_after(self.day) {
+            treaty.status = TreatyStatus::Fulfilled;
+            self.logged_actions.treaty_update(treaty);
+        }
+        Ok(())
+    }
+
+    /// Turns down a proposal, either the counterpart declining it or the proposer withdrawing it
+    pub fn reject_treaty(&mut self, player: &str, id: u64) -> Result<(), WoopError> {
+        let treaty = match self.treaties.get_mut(id) {
+            Some(treaty) if treaty.status == TreatyStatus::Proposed => treaty,
+            _ => return Err(WoopError::TreatyNotFound(id)),
+        };
+        treaty.status = if treaty.counterpart == player {
+            TreatyStatus::Declined
+        } else if treaty.proposer == player {
+            TreatyStatus::Withdrawn
+        } else {
+            return Err(WoopError::TreatyNotFound(id));
+        };
+        self.logged_actions.treaty_update(treaty);
+        Ok(())
+    }
+
+    // Hands over the escrowed points due today and closes the treaties with nothing left to honor
+    fn settle_treaties(&mut self) {
+        for treaty in self.treaties.iter_mut() {
+            if treaty.status != TreatyStatus::Active {
+                continue;
+            }
+            let (due, held) = treaty
+                .escrow
+                .drain(..)
+                .partition(|escrow| escrow.day <= self.day);
+            treaty.escrow = held;
+            for escrow in due {
+                if let Some(to) = self.players.get_mut(&escrow.to) {
+                    to.points += escrow.amount;
+                }
+                self.logged_actions.treaty_points(
+                    treaty.id,
+                    None,
+                    Some(escrow.to.as_str()),
+                    escrow.amount,
+                );
+            }
+            if treaty.done_after(self.day - 1) {
+                treaty.status = TreatyStatus::Fulfilled;
+                self.logged_actions.treaty_update(treaty);
+            }
+        }
+    }
+
+    // A shot at a player `shooter` promised not to attack breaks their treaties: the escrow of the
+    // shooter goes to the target and the target gets theirs back
+    fn break_treaties(&mut self, shooter: &str, target: &str) {
+        for treaty in self
+            .treaties
+            .iter_mut()
+            .filter(|treaty| treaty.forbids_shot(shooter, target, self.day))
+        {
+            treaty.status = TreatyStatus::Broken {
+                by: shooter.to_string(),
+            };
+            for escrow in treaty.escrow.drain(..) {
+                if let Some(to) = self.players.get_mut(target) {
+                    to.points += escrow.amount;
+                }
+                self.logged_actions
+                    .treaty_points(treaty.id, None, Some(target), escrow.amount);
+            }
+            self.logged_actions.treaty_update(treaty);
+        }
+    }
+
+    /// Whether `viewer` gets to know about `live`
+    pub fn sees_live(&self, viewer: Option<&str>, live: &Live) -> bool {
+        match live {
+            Live::Event(event) => Sight::new(self, viewer).sees_event(event),
+            Live::Message(message) => {
+                viewer.is_some_and(|viewer| self.messages.conversation(message, viewer).is_some())
+            }
+        }
+    }
+
     pub fn generate_shield(&mut self, player: &str, x: i16, y: i16) -> Result<(), WoopError> {
         // Check if zord in cell
         let zord = match self
@@ -87,7 +682,7 @@ impl Game {
 
         // Check if enough actions
         let owner = self.players.get_mut(name).unwrap();
-        if owner.actions == 0 {
+        if owner.actions < ACTION_COST {
             return WoopError::out_of_actions();
         }
         owner.spend_action(ACTION_COST);
@@ -104,7 +699,7 @@ impl Game {
         }
 
         // Target exist
-        if self.players.get(to).is_none() {
+        if !self.players.contains_key(to) {
             return WoopError::player_not_found(to);
         }
 
@@ -119,18 +714,24 @@ impl Game {
         }
 
         // Check if within range for donation (range for donations is shared with the range for
-        // shooting
+        // shooting, the team rules can give allies a longer one)
+        let ally_range = self
+            .team_rules
+            .ally_donation_range
+            .filter(|_| self.teams.allies(from, to));
         let mut s_zord = self.zords.iter().filter(|zord| zord.owner.as_str() == from);
         let mut t_zord = self.zords.iter().filter(|zord| zord.owner.as_str() == to);
 
-        let is_in_range = s_zord
-            .any(|s| t_zord.any(|t| (s.x - t.x).abs().max((s.y - t.y).abs()) <= s.range as i16));
+        let is_in_range = s_zord.any(|s| {
+            let range = ally_range.map_or(s.range as u16, |ally| ally.max(s.range as u16)) as i16;
+            t_zord.any(|t| distance((s.x, s.y), (t.x, t.y)) <= range)
+        });
         if !is_in_range {
             return WoopError::donation_out_of_range();
         }
 
         // Has enough actions
-        if pf.actions == 0 {
+        if pf.actions < ACTION_COST {
             return WoopError::out_of_actions();
         }
         pf.spend_action(ACTION_COST);
@@ -139,7 +740,7 @@ impl Game {
         let pt = self.players.get_mut(to).unwrap();
         pt.points += amount;
 
-        self.logged_actions.donate_points(from, to);
+        self.logged_actions.donate_points(from, to, amount);
         Ok(())
     }
 
@@ -174,13 +775,13 @@ impl Game {
         let owner = self.players.get_mut(player).unwrap();
 
         // Check if within range
-        let distance = (x_f - x_t).abs().max((y_f - y_t).abs());
+        let distance = distance((x_f, y_f), (x_t, y_t));
         if distance > owner.actions as i16 {
             return WoopError::not_in_range(x_f, y_f, x_t, y_t);
         }
 
         // Check if in bounds
-        if x_t < 0 || x_t >= BASE_BOARD_SIZE || y_t < 0 || y_t >= BASE_BOARD_SIZE {
+        if !(0..BASE_BOARD_SIZE).contains(&x_t) || !(0..BASE_BOARD_SIZE).contains(&y_t) {
             return WoopError::out_of_bounds(x_t, y_t);
         }
 
@@ -220,56 +821,74 @@ impl Game {
 
         // Check if within range
         let range = zord.range;
-        let distance = (x_f - x_t).abs().max((y_f - y_t).abs());
-        if distance > range as i16 {
+        if distance((x_f, y_f), (x_t, y_t)) > range as i16 {
             return WoopError::not_in_range(x_f, y_f, x_t, y_t);
         }
 
         // Check grace period
-        let delta_t = self.start_of_day.elapsed();
-        if delta_t.unwrap().as_secs() <= GRACE_PERIOD {
+        if self.within_grace_period() {
             return WoopError::within_grace_period();
         }
 
         // Check if enough actions
         let owner = self.players.get_mut(player).unwrap();
-        if owner.actions == 0 {
+        if owner.actions < ACTION_COST {
             return WoopError::out_of_actions();
         }
 
         // Check if target is your own
-        let target = self
+        let target = match self
             .zords
             .iter_mut()
             .find(|zord| zord.x == x_t && zord.y == y_t)
-            .unwrap();
+        {
+            None => return WoopError::zord_not_found(x_t, y_t),
+            Some(z) => z,
+        };
 
         if target.owner.as_str() == player {
             return WoopError::own_zord();
         }
 
+        if self.team_rules.no_friendly_fire && self.teams.allies(player, target.owner.as_str()) {
+            return Err(WoopError::FriendlyFire);
+        }
+
         owner.spend_action(ACTION_COST);
 
         // Shoot and cleanup
         let t_name = target.owner.clone();
-        if target.hit() {
-            owner.points += KILL_REWARD;
-        }
+        let shielded = target.shields > 0;
+        let destroyed = target.hit();
+        let hp_left = target.hp;
+        let reward = if destroyed { KILL_REWARD } else { 0 };
+        owner.points += reward;
         self.clear_dead();
 
-        let has_zords = self
-            .zords
-            .iter()
-            .filter(|zord| zord.owner == t_name)
-            .count()
-            > 0;
-        if !has_zords {
+        let wiped_out = !self.zords.iter().any(|zord| zord.owner == t_name);
+        self.logged_actions.shoot(
+            player,
+            (x_f, y_f),
+            (x_t, y_t),
+            t_name.as_str(),
+            ShotOutcome {
+                shielded,
+                hp_left,
+                destroyed,
+                reward,
+                wiped_out,
+            },
+        );
+        if wiped_out {
             let t_player = self.players.get_mut(&t_name).unwrap();
-            t_player.points = t_player.points * 2 / 3;
+            let points_left = t_player.points * 2 / 3;
+            let lost = t_player.points - points_left;
+            t_player.points = points_left;
+            self.logged_actions
+                .point_penalty(t_name.as_str(), lost, points_left);
         }
-
-        self.logged_actions
-            .shoot(player, (x_f, y_f), (x_t, y_t), t_name.as_str());
+        // After the penalty, which follows the shot right away in the log
+        self.break_treaties(player, t_name.as_str());
         Ok(())
     }
 
@@ -285,28 +904,37 @@ impl Game {
     }
 
     fn give_out_totem_points(&mut self) {
+        let pooled = self.team_rules.pooled_totems;
         let mut points = |totem: &Totem| {
-            let mut in_bounds = HashMap::new();
+            // Who shares the points of each zord, with pooled totems it's the whole team
+            let mut in_bounds: BTreeMap<Vec<String>, u16> = BTreeMap::new();
             let mut total = 0;
             self.zords.iter().for_each(|z| {
-                if (totem.x - z.x).abs().max((totem.y - z.y).abs()) <= TOTEM_AURA as i16 {
-                    match in_bounds.get(z.owner.as_str()) {
-                        None => in_bounds.insert(z.owner.clone(), 1),
-                        Some(v) => in_bounds.insert(z.owner.clone(), v + 1),
+                if distance((totem.x, totem.y), (z.x, z.y)) <= TOTEM_AURA as i16 {
+                    let earners = match self.teams.team_of(z.owner.as_str()) {
+                        Some(team) if pooled => {
+                            self.teams.members(team).unwrap().iter().cloned().collect()
+                        }
+                        _ => vec![z.owner.clone()],
                     };
+                    *in_bounds.entry(earners).or_insert(0) += 1;
                     total += 1;
                 }
             });
 
-            for player in in_bounds.keys() {
-                let many = in_bounds.get(player).unwrap();
-                let p = self.players.get_mut(player).unwrap();
-                p.points += TOTEM_REWARD * many / total;
-                self.logged_actions.totem_points(
-                    player.as_str(),
-                    (totem.x, totem.y),
-                    TOTEM_REWARD * many / total,
-                );
+            for (earners, many) in in_bounds.iter() {
+                // What's left of a split goes a point each to some of the earners, starting from
+                // a different one every day
+                let points = TOTEM_REWARD * many / total;
+                let (share, left) = (points / earners.len() as u16, points % earners.len() as u16);
+                for (i, player) in earners.iter().enumerate() {
+                    let turn = (i + self.day as usize) % earners.len();
+                    let share = share + (turn < left as usize) as u16;
+                    let p = self.players.get_mut(player).unwrap();
+                    p.points += share;
+                    self.logged_actions
+                        .totem_points(player.as_str(), (totem.x, totem.y), share);
+                }
             }
         };
         points(&self.totems.0);
@@ -314,8 +942,8 @@ impl Game {
     }
 
     fn respawn_players(&mut self) {
-        let mut rng = rng();
-        let mut players: HashMap<String, i32> =
+        // Ordered, so that the same seed always respawns the players in the same order
+        let mut players: BTreeMap<String, i32> =
             self.players.keys().map(|name| (name.clone(), 0)).collect();
 
         self.zords.iter().for_each(|z| {
@@ -329,7 +957,7 @@ impl Game {
             .map(|(p, _)| p)
             .collect();
         while !to_spawn.is_empty() {
-            let player = to_spawn.remove(rng.random_range(0..to_spawn.len()));
+            let player = to_spawn.remove(self.rng.random_range(0..to_spawn.len()));
             let (x, y) = self.calculate_respawn_coordinates();
             self.create_zord(player.as_str(), x, y);
             self.logged_actions.respawn(player, (x, y));
@@ -339,9 +967,25 @@ impl Game {
     // Spawning totems before players gives a more interesting map disposition (this given the fact
     // that we also include totems in the algorithm to choose the spawn point for the players)
     pub fn new_day(&mut self) {
+        if self.day > 0 {
+            let mut players: Vec<Player> = self.players.values().cloned().collect();
+            players.sort_by(|a, b| a.name.cmp(&b.name));
+            self.history.push(DayState {
+                day: self.day,
+                players,
+                zords: self.zords.clone(),
+                totems: Some(self.totems.clone()),
+                ended_at: self.clock.unix_timestamp(),
+            });
+        }
+
         // Set new day
-        self.start_of_day = SystemTime::now();
+        self.start_of_day = self.clock.now();
         self.day += 1;
+        self.logged_actions.new_day(self.day);
+        self.orders.new_day();
+        self.replies.clear();
+        self.settle_treaties();
 
         self.give_out_totem_points();
 
@@ -358,10 +1002,24 @@ impl Game {
             .for_each(|(_, player)| player.actions = BASE_ACTIONS);
 
         // Remove shields and reset range
-        self.zords.iter_mut().for_each(|entity| {
+        for entity in self.zords.iter_mut() {
+            if entity.shields > 0 {
+                self.logged_actions.shield_expired(
+                    entity.owner.as_str(),
+                    (entity.x, entity.y),
+                    entity.shields,
+                );
+            }
+            if entity.range != BASE_RANGE {
+                self.logged_actions.range_reset(
+                    entity.owner.as_str(),
+                    (entity.x, entity.y),
+                    entity.range,
+                );
+            }
             entity.range = BASE_RANGE;
             entity.shields = 0;
-        });
+        }
     }
 
     pub fn increase_range(&mut self, player: &str, x: i16, y: i16) -> Result<(), WoopError> {
@@ -382,7 +1040,7 @@ impl Game {
 
         // Check if enough actions
         let owner = self.players.get_mut(player).unwrap();
-        if owner.actions == 0 {
+        if owner.actions < ACTION_COST / 2 {
             return WoopError::out_of_actions();
         }
 
@@ -393,15 +1051,11 @@ impl Game {
     }
 
     pub fn build_zord(&mut self, player: &str, x: i16, y: i16) -> Result<(), WoopError> {
-        fn distance(z: &Zord, x: i16, y: i16) -> i16 {
-            (z.x - x).abs().max((z.y - y).abs())
-        }
-
         // Check if (x, y) is nearby another zord
         if !self
             .zords
             .iter()
-            .any(|z| distance(z, x, y) <= 1 && z.owner == player)
+            .any(|z| distance((z.x, z.y), (x, y)) <= 1 && z.owner == player)
         {
             return WoopError::no_zord_nearby(x, y);
         }
@@ -410,7 +1064,7 @@ impl Game {
         if self
             .players
             .iter()
-            .any(|(_, p)| p.name == player && p.actions == 0)
+            .any(|(_, p)| p.name == player && p.actions < ACTION_COST)
         {
             return WoopError::out_of_actions();
         }
@@ -431,7 +1085,8 @@ impl Game {
         self.create_zord(player, x, y);
         self.zords.last_mut().unwrap().hit();
 
-        self.logged_actions.build_zord(player, (x, y));
+        self.logged_actions
+            .build_zord(player, (x, y), NEW_ZORD_COST);
         Ok(())
     }
 
@@ -439,7 +1094,7 @@ impl Game {
     fn calculate_respawn_coordinates(&self) -> (i16, i16) {
         let mut ris = (0, 0);
         let mut r_dis = 0;
-        if self.zords.len() == 0 {
+        if self.zords.is_empty() {
             return ris;
         }
 
@@ -450,14 +1105,14 @@ impl Game {
 
         for y_f in 0..BASE_BOARD_SIZE {
             for x_f in 0..BASE_BOARD_SIZE {
-                let distance = entities_on_board
+                let closest = entities_on_board
                     .iter()
-                    .map(|(x, y)| ((x_f - x).abs().max((y_f - y).abs()), x, y))
+                    .map(|(x, y)| (distance((x_f, y_f), (*x, *y)), x, y))
                     .min()
                     .unwrap();
-                if r_dis < distance.0 {
+                if r_dis < closest.0 {
                     ris = (x_f, y_f);
-                    r_dis = distance.0;
+                    r_dis = closest.0;
                 }
             }
         }
@@ -465,7 +1120,7 @@ impl Game {
     }
 
     fn spawn_totems(&mut self) {
-        let mut rng = rng();
+        let rng = &mut self.rng;
         loop {
             let t1 = (
                 rng.random_range(0..BASE_BOARD_SIZE),
@@ -473,8 +1128,7 @@ impl Game {
             );
             let t2 = ((BASE_BOARD_SIZE - 1) - t1.0, (BASE_BOARD_SIZE - 1) - t1.1);
 
-            let is_far_enough =
-                (t1.0 - t2.0).abs().max((t1.1 - t2.1).abs()) as u16 > TOTEM_AURA * 2;
+            let is_far_enough = distance(t1, t2) as u16 > TOTEM_AURA * 2;
 
             if is_far_enough {
                 self.totems = (Totem::new(t1.0, t1.1), Totem::new(t2.0, t2.1));
@@ -488,27 +1142,56 @@ impl Game {
 
 #[cfg(test)]
 mod tests {
-    use super::Game;
+    use super::{ChaCha8Rng, Game, SeedableRng};
     use crate::{
         config::Config,
         game::{
-            game::{BASE_BOARD_SIZE, GRACE_PERIOD},
+            action::Action,
+            clock::Clock,
+            error::WoopError,
+            game::{BASE_BOARD_SIZE, GRACE_PERIOD, STREAM_TICKET_TTL},
+            log::{EventQuery, PlayerEvent},
+            orders::Order,
             player::BASE_ACTIONS,
+            replies::{fingerprint, MAX_KEYS},
+            team::TeamRules,
             totem::Totem,
+            treaty::{Term, TreatyStatus, MAX_PROPOSALS},
             zord::BASE_RANGE,
         },
     };
-    use std::time::{Duration, SystemTime};
+    use std::time::{Duration, UNIX_EPOCH};
 
     fn generate_game() -> Game {
-        let config = Config {
-            players: ["mroik", "fin", "warden"]
+        let config = Config::with_players("test", &["mroik", "fin", "warden"]);
+        Game::new(&config)
+    }
+
+    fn seeded_game(seed: u64) -> Game {
+        let mut game = generate_game();
+        game.seed = seed;
+        game.rng = ChaCha8Rng::seed_from_u64(seed);
+        game
+    }
+
+    #[test]
+    fn same_seed_same_game() {
+        let mut games = [seeded_game(42), seeded_game(42)];
+        for game in games.iter_mut() {
+            game.new_day();
+            game.zords.retain(|zord| zord.owner != "fin");
+            game.new_day();
+        }
+        let [a, b] = &games;
+        assert_eq!((a.totems.0.x, a.totems.0.y), (b.totems.0.x, b.totems.0.y));
+        let coords = |game: &Game| -> Vec<(String, i16, i16)> {
+            game.zords
                 .iter()
-                .map(|s| String::from(*s))
-                .collect(),
-            start_of_game: SystemTime::now(),
+                .map(|zord| (zord.owner.clone(), zord.x, zord.y))
+                .collect()
         };
-        Game::new(&config)
+        assert_eq!(coords(a), coords(b));
+        assert_ne!(a.auth, b.auth);
     }
 
     #[test]
@@ -540,6 +1223,240 @@ mod tests {
         assert_eq!(t_points, 100 * 2 / 3);
     }
 
+    #[test]
+    fn shoot_logs_outcome() {
+        let mut game = generate_game();
+        game.start_of_day = game
+            .start_of_day
+            .checked_sub(Duration::from_secs(GRACE_PERIOD + 1))
+            .unwrap();
+        game.create_zord("mroik", 0, 0);
+        game.create_zord("fin", 1, 1);
+        game.players.get_mut("fin").unwrap().points = 100;
+        game.zords.last_mut().unwrap().shields = 1;
+        for _ in 0..3 {
+            game.player_shoot("mroik", 0, 0, 1, 1).unwrap();
+        }
+
+        let events = game.logged_actions.query(&EventQuery::default()).events;
+        let outcomes: Vec<(bool, u8, bool, u16, bool)> = events
+            .iter()
+            .rev()
+            .filter_map(|event| match event {
+                PlayerEvent::Shoot {
+                    shielded,
+                    hp_left,
+                    destroyed,
+                    reward,
+                    wiped_out,
+                    ..
+                } => Some((*shielded, *hp_left, *destroyed, *reward, *wiped_out)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            outcomes,
+            vec![
+                (true, 2, false, 0, false),
+                (false, 1, false, 0, false),
+                (false, 0, true, 3, true),
+            ]
+        );
+        match &events[0] {
+            PlayerEvent::PointPenalty {
+                player,
+                points,
+                points_left,
+                ..
+            } => {
+                assert_eq!(player, "fin");
+                assert_eq!(*points, 34);
+                assert_eq!(*points_left, 66);
+            }
+            event => panic!("Expected a point penalty, got {:?}", event),
+        }
+    }
+
+    #[test]
+    fn preview_shot() {
+        let mut game = generate_game();
+        game.start_of_day = game
+            .start_of_day
+            .checked_sub(Duration::from_secs(GRACE_PERIOD + 1))
+            .unwrap();
+        game.create_zord("mroik", 0, 0);
+        game.create_zord("fin", 1, 1);
+        game.zords[1].hp = 1;
+        let shot = Action::Shoot {
+            from: (0, 0),
+            to: (1, 1),
+        };
+
+        let preview = game.preview("mroik", &shot).unwrap();
+        assert_eq!(preview.cost, 4);
+        assert_eq!(preview.actions_left, BASE_ACTIONS - 4);
+        assert_eq!(preview.points_left, 3);
+        assert_eq!(preview.events[0].id(), 0);
+        match &preview.events[0] {
+            PlayerEvent::Shoot {
+                destroyed, reward, ..
+            } => assert!(*destroyed && *reward == 3),
+            event => panic!("Expected a shot, got {:?}", event),
+        }
+        assert_eq!(game.zords.len(), 2);
+        assert_eq!(game.players["mroik"].actions, BASE_ACTIONS);
+        assert_eq!(game.logged_actions.oldest_first().count(), 0);
+
+        let too_far = Action::Shoot {
+            from: (0, 0),
+            to: (9, 9),
+        };
+        assert!(matches!(
+            game.preview("mroik", &too_far),
+            Err(WoopError::NotInRange(..))
+        ));
+    }
+
+    #[test]
+    fn batch_all_or_nothing() {
+        let mut game = generate_game();
+        game.create_zord("mroik", 0, 0);
+        let actions = [
+            Action::Move {
+                from: (0, 0),
+                to: (2, 2),
+            },
+            Action::Shield { coord: (2, 2) },
+            Action::Shield { coord: (0, 0) },
+        ];
+
+        let results = game.apply_batch("mroik", &actions, false);
+        assert_eq!(results.len(), 3);
+        assert!(matches!(results[2], Err(WoopError::ZordNotFound(0, 0))));
+        assert_eq!((game.zords[0].x, game.zords[0].y), (0, 0));
+        assert_eq!(game.players["mroik"].actions, BASE_ACTIONS);
+        assert_eq!(game.logged_actions.oldest_first().count(), 0);
+
+        let results = game.apply_batch("mroik", &actions, true);
+        assert_eq!(results.len(), 3);
+        assert_eq!((game.zords[0].x, game.zords[0].y), (2, 2));
+        assert_eq!(game.zords[0].shields, 1);
+        assert_eq!(game.players["mroik"].actions, BASE_ACTIONS - 2 - 4);
+        assert_eq!(game.logged_actions.oldest_first().count(), 2);
+
+        let results = game.apply_batch("mroik", &actions[1..2], false);
+        assert!(results.iter().all(|result| result.is_ok()));
+        assert_eq!(game.zords[0].shields, 2);
+    }
+
+    #[test]
+    fn run_due_orders() {
+        let mut game = generate_game();
+        game.create_zord("mroik", 0, 0);
+        game.create_zord("fin", 3, 3);
+        let shield = Action::Shield { coord: (0, 0) };
+        let orders = [
+            Order::ShootInRange { coord: (0, 0) },
+            Order::At {
+                time: 0,
+                action: shield.clone(),
+            },
+            Order::At {
+                time: u64::MAX,
+                action: shield,
+            },
+        ];
+        for order in orders {
+            game.orders.add("mroik", order).unwrap();
+        }
+
+        assert_eq!(game.run_orders(), 1);
+        assert_eq!(game.zords[0].shields, 1);
+        game.start_of_day = game
+            .start_of_day
+            .checked_sub(Duration::from_secs(GRACE_PERIOD + 1))
+            .unwrap();
+        assert_eq!(game.run_orders(), 1);
+        assert_eq!(game.zords[1].hp, 1);
+        assert_eq!(game.orders.pending("mroik").count(), 1);
+        assert!(game
+            .orders
+            .played("mroik")
+            .all(|played| played.error.is_none()));
+
+        game.new_day();
+        assert_eq!(game.orders.played("mroik").count(), 0);
+    }
+
+    #[test]
+    fn stream_tickets() {
+        let mut game = generate_game();
+        let clock = Clock::manual(UNIX_EPOCH);
+        game.set_clock(clock.clone());
+
+        let ticket = game.stream_ticket("mroik");
+        assert_eq!(ticket.expires_at, STREAM_TICKET_TTL);
+        assert_eq!(game.redeem_stream_ticket(&ticket.ticket).unwrap(), "mroik");
+        assert!(game.redeem_stream_ticket(&ticket.ticket).is_err());
+        assert!(game.redeem_stream_ticket("made_up").is_err());
+
+        let ticket = game.stream_ticket("fin");
+        clock.advance(Duration::from_secs(STREAM_TICKET_TTL));
+        assert!(game.redeem_stream_ticket(&ticket.ticket).is_err());
+    }
+
+    #[test]
+    fn replies_expire_at_rollover() {
+        let mut game = generate_game();
+        let shot = fingerprint("shoot", &serde_json::json!({"from": [0, 0], "to": [1, 1]}));
+        game.replies.insert(
+            "mroik",
+            String::from("retry"),
+            shot.clone(),
+            serde_json::json!("ok"),
+        );
+        assert!(game
+            .replies
+            .get("mroik", Some("retry"), &shot)
+            .unwrap()
+            .is_some());
+        assert!(game
+            .replies
+            .get("fin", Some("retry"), &shot)
+            .unwrap()
+            .is_none());
+        assert!(game.replies.get("mroik", None, &shot).unwrap().is_none());
+        let moved = fingerprint("move", &serde_json::json!({"from": [0, 0], "to": [1, 1]}));
+        assert!(matches!(
+            game.replies.get("mroik", Some("retry"), &moved),
+            Err(WoopError::KeyReused(_))
+        ));
+
+        for key in 1..MAX_KEYS {
+            let reply = serde_json::json!("ok");
+            game.replies
+                .insert("mroik", key.to_string(), shot.clone(), reply);
+        }
+        assert!(game.replies.get("mroik", Some("1"), &shot).is_ok());
+        assert!(matches!(
+            game.replies.get("mroik", Some("new"), &shot),
+            Err(WoopError::TooManyKeys)
+        ));
+        assert!(game.replies.get("fin", Some("new"), &shot).is_ok());
+
+        game.new_day();
+        assert!(game
+            .replies
+            .get("mroik", Some("retry"), &shot)
+            .unwrap()
+            .is_none());
+        assert!(game
+            .replies
+            .get("mroik", Some("new"), &shot)
+            .unwrap()
+            .is_none());
+    }
+
     #[test]
     fn shoot_during_grace_period() {
         let mut game = generate_game();
@@ -582,6 +1499,30 @@ mod tests {
         assert_eq!(game.players.get("fin").unwrap().points, 0);
     }
 
+    #[test]
+    fn shoot_empty_cell() {
+        let mut game = generate_game();
+        game.start_of_day = game
+            .start_of_day
+            .checked_sub(Duration::from_secs(GRACE_PERIOD + 1))
+            .unwrap();
+        game.create_zord("mroik", 0, 0);
+        let success = game.player_shoot("mroik", 0, 0, 1, 1);
+        assert!(success.is_err());
+        assert_eq!(game.players.get("mroik").unwrap().actions, BASE_ACTIONS);
+    }
+
+    #[test]
+    fn generate_shield_not_enough_actions() {
+        let mut game = generate_game();
+        game.create_zord("mroik", 0, 0);
+        game.players.get_mut("mroik").unwrap().actions = 2;
+        let success = game.generate_shield("mroik", 0, 0);
+        assert!(success.is_err());
+        assert_eq!(game.players.get("mroik").unwrap().actions, 2);
+        assert_eq!(game.zords.first().unwrap().shields, 0);
+    }
+
     #[test]
     fn generate_shield() {
         let mut game = generate_game();
@@ -628,6 +1569,18 @@ mod tests {
         assert_eq!(zord.range, BASE_RANGE);
         assert_eq!(zord.shields, 0);
         assert_eq!(game.zords.len(), 3);
+        let query = EventQuery {
+            kind: Some(String::from("shield_expired,range_reset")),
+            ..Default::default()
+        };
+        let kinds: Vec<&str> = game
+            .logged_actions
+            .query(&query)
+            .events
+            .iter()
+            .map(|event| event.kind())
+            .collect();
+        assert_eq!(kinds, vec!["range_reset", "shield_expired"]);
     }
 
     #[test]
@@ -687,6 +1640,233 @@ mod tests {
         assert_eq!(game.players.get("fin").unwrap().points, 10);
     }
 
+    #[test]
+    fn team_rules() {
+        let mut game = generate_game();
+        game.start_of_day = game
+            .start_of_day
+            .checked_sub(Duration::from_secs(GRACE_PERIOD + 1))
+            .unwrap();
+        game.team_rules = TeamRules {
+            no_friendly_fire: true,
+            ally_donation_range: Some(50),
+            pooled_totems: false,
+        };
+        game.players.get_mut("mroik").unwrap().points = 100;
+        game.create_zord("mroik", 0, 0);
+        game.create_zord("fin", 1, 1);
+        game.create_zord("warden", 40, 40);
+
+        game.create_team("mroik", "red").unwrap();
+        game.invite_to_team("mroik", "fin").unwrap();
+        game.invite_to_team("mroik", "warden").unwrap();
+        game.join_team("fin", "red").unwrap();
+        assert!(matches!(
+            game.player_shoot("mroik", 0, 0, 1, 1),
+            Err(WoopError::FriendlyFire)
+        ));
+        assert!(game.donate_points("mroik", "warden", 10).is_err());
+        game.join_team("warden", "red").unwrap();
+        game.donate_points("mroik", "warden", 10).unwrap();
+
+        game.leave_team("fin").unwrap();
+        game.player_shoot("mroik", 0, 0, 1, 1).unwrap();
+
+        let kinds: Vec<&str> = game
+            .logged_actions
+            .oldest_first()
+            .map(|event| event.kind())
+            .filter(|kind| kind.starts_with("team"))
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                "team_created",
+                "team_invite",
+                "team_invite",
+                "team_joined",
+                "team_joined",
+                "team_left"
+            ]
+        );
+
+        // A shorter ally range doesn't take away the range of the zords
+        game.team_rules.ally_donation_range = Some(1);
+        game.create_zord("warden", 3, 3);
+        game.donate_points("mroik", "warden", 10).unwrap();
+    }
+
+    #[test]
+    fn treaty_proposals_are_capped() {
+        let mut game = generate_game();
+        let truce = || vec![Term::NonAggression { until: 1 }];
+        for _ in 0..MAX_PROPOSALS {
+            game.propose_treaty("mroik", "fin", truce(), vec![])
+                .unwrap();
+        }
+        assert!(matches!(
+            game.propose_treaty("mroik", "fin", truce(), vec![]),
+            Err(WoopError::TooManyProposals)
+        ));
+        game.propose_treaty("fin", "mroik", truce(), vec![])
+            .unwrap();
+        game.reject_treaty("fin", 0).unwrap();
+        game.propose_treaty("mroik", "warden", truce(), vec![])
+            .unwrap();
+    }
+
+    #[test]
+    fn treaties() {
+        let mut game = generate_game();
+        game.start_of_day = game
+            .start_of_day
+            .checked_sub(Duration::from_secs(GRACE_PERIOD + 1))
+            .unwrap();
+        game.day = 3;
+        let day = game.day;
+        let points = |game: &Game, player: &str| game.players[player].points;
+        game.players.get_mut("mroik").unwrap().points = 100;
+        game.players.get_mut("fin").unwrap().points = 5;
+        game.create_zord("mroik", 0, 0);
+        game.create_zord("fin", 1, 1);
+
+        assert!(matches!(
+            game.propose_treaty(
+                "mroik",
+                "fin",
+                vec![Term::NonAggression { until: day - 1 }],
+                vec![]
+            ),
+            Err(WoopError::InvalidTreaty(_))
+        ));
+        let truce = game
+            .propose_treaty(
+                "mroik",
+                "fin",
+                vec![
+                    Term::Points { amount: 10 },
+                    Term::NonAggression { until: day },
+                ],
+                vec![Term::PointsOnDay {
+                    amount: 20,
+                    day: day + 1,
+                }],
+            )
+            .unwrap()
+            .id;
+        assert!(matches!(
+            game.accept_treaty("mroik", truce),
+            Err(WoopError::TreatyNotFound(_))
+        ));
+        assert!(matches!(
+            game.accept_treaty("fin", truce),
+            Err(WoopError::TreatyUnaffordable(_))
+        ));
+        game.players.get_mut("fin").unwrap().points = 50;
+        game.accept_treaty("fin", truce).unwrap();
+        assert_eq!(points(&game, "mroik"), 90);
+        assert_eq!(points(&game, "fin"), 40);
+
+        let loan = game
+            .propose_treaty(
+                "fin",
+                "warden",
+                vec![Term::PointsOnDay {
+                    amount: 5,
+                    day: day + 1,
+                }],
+                vec![],
+            )
+            .unwrap()
+            .id;
+        game.accept_treaty("warden", loan).unwrap();
+        let refused = game
+            .propose_treaty("fin", "mroik", vec![], vec![Term::Points { amount: 1 }])
+            .unwrap()
+            .id;
+        game.reject_treaty("mroik", refused).unwrap();
+        assert!(game.reject_treaty("fin", refused).is_err());
+
+        // Breaking the truce gives fin back the points held in escrow
+        game.player_shoot("mroik", 0, 0, 1, 1).unwrap();
+        assert_eq!(points(&game, "fin"), 55);
+
+        game.day += 1;
+        game.settle_treaties();
+        assert_eq!(points(&game, "warden"), 5);
+        let statuses: Vec<TreatyStatus> = game
+            .treaties
+            .iter()
+            .map(|treaty| treaty.status.clone())
+            .collect();
+        assert_eq!(
+            statuses,
+            vec![
+                TreatyStatus::Broken {
+                    by: String::from("mroik")
+                },
+                TreatyStatus::Fulfilled,
+                TreatyStatus::Declined
+            ]
+        );
+        let breaches = game
+            .logged_actions
+            .oldest_first()
+            .filter(|event| {
+                matches!(
+                    event,
+                    PlayerEvent::TreatyUpdate {
+                        status: TreatyStatus::Broken { .. },
+                        ..
+                    }
+                )
+            })
+            .count();
+        assert_eq!(breaches, 1);
+    }
+
+    #[test]
+    fn wipe_out_breaking_a_treaty() {
+        let mut game = generate_game();
+        game.start_of_day = game
+            .start_of_day
+            .checked_sub(Duration::from_secs(GRACE_PERIOD + 1))
+            .unwrap();
+        game.day = 3;
+        game.players.get_mut("mroik").unwrap().points = 20;
+        game.players.get_mut("fin").unwrap().points = 30;
+        game.create_zord("mroik", 0, 0);
+        game.create_zord("fin", 1, 1);
+        game.zords[1].hp = 1;
+        let truce = game
+            .propose_treaty(
+                "mroik",
+                "fin",
+                vec![
+                    Term::PointsOnDay { amount: 10, day: 4 },
+                    Term::NonAggression { until: 3 },
+                ],
+                vec![],
+            )
+            .unwrap()
+            .id;
+        game.accept_treaty("fin", truce).unwrap();
+
+        // The penalty comes right after the shot and leaves out the escrow handed over after it
+        game.player_shoot("mroik", 0, 0, 1, 1).unwrap();
+        assert_eq!(game.players["fin"].points, 30);
+        let kinds: Vec<&str> = game
+            .logged_actions
+            .newest_first()
+            .take(4)
+            .map(|event| event.kind())
+            .collect();
+        assert_eq!(
+            kinds,
+            vec!["treaty_update", "treaty_points", "point_penalty", "shoot"]
+        );
+    }
+
     #[test]
     fn donate_points_amount_too_big() {
         let mut game = generate_game();
@@ -766,6 +1946,31 @@ mod tests {
         assert_eq!(game.players.get("fin").unwrap().points, 25);
     }
 
+    #[test]
+    fn give_out_points_pooled() {
+        let mut game = generate_game();
+        game.team_rules.pooled_totems = true;
+        game.create_zord("mroik", 0, 0);
+        game.create_zord("fin", 0, 1);
+        game.create_team("mroik", "red").unwrap();
+        game.invite_to_team("mroik", "warden").unwrap();
+        game.join_team("warden", "red").unwrap();
+        game.totems = (
+            Totem::new(2, 2),
+            Totem::new(BASE_BOARD_SIZE - 1, BASE_BOARD_SIZE - 1),
+        );
+        game.give_out_totem_points();
+        assert_eq!(game.players.get("mroik").unwrap().points, 13);
+        assert_eq!(game.players.get("warden").unwrap().points, 12);
+        assert_eq!(game.players.get("fin").unwrap().points, 25);
+
+        // The point left over goes to the other member the next day
+        game.day += 1;
+        game.give_out_totem_points();
+        assert_eq!(game.players.get("mroik").unwrap().points, 25);
+        assert_eq!(game.players.get("warden").unwrap().points, 25);
+    }
+
     #[test]
     fn give_out_out_of_range() {
         let mut game = generate_game();
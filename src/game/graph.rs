@@ -0,0 +1,230 @@
+use super::{log::PlayerEvent, stats::tally};
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Write},
+};
+use utoipa::ToSchema;
+
+#[derive(Debug, Default, Clone, Serialize, ToSchema)]
+pub struct Interactions {
+    pub shots: u64,
+    pub kills: u64,
+    pub donations: u64,
+    pub points_donated: u64,
+}
+
+/// Everything a player did to another one
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Edge {
+    pub from: String,
+    pub to: String,
+    pub total: Interactions,
+    /// Interactions of each game day
+    pub days: BTreeMap<u8, Interactions>,
+}
+
+/// A player shooting someone they exchanged points with
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Betrayal {
+    pub traitor: String,
+    pub victim: String,
+    /// Id of the shot
+    pub event: u64,
+    pub day: u8,
+    /// Id of the last donation between the two players before the shot
+    pub donation: u64,
+    /// The traitor received the donation instead of making it
+    pub was_funded: bool,
+}
+
+/// Who shot and who funded whom, updated one event at a time as they are logged
+#[derive(Debug, Default)]
+pub struct InteractionGraph {
+    edges: BTreeMap<(String, String), Edge>,
+    // Last donation between two players, keyed by (donor, receiver)
+    donations: BTreeMap<(String, String), u64>,
+    // Last shot flagged as betrayal, keyed by (traitor, victim)
+    flagged: BTreeMap<(String, String), u64>,
+    betrayals: Vec<Betrayal>,
+}
+
+impl InteractionGraph {
+    pub fn record(&mut self, event: &PlayerEvent) {
+        match event {
+            PlayerEvent::Shoot {
+                id,
+                day,
+                shooter,
+                target,
+                destroyed,
+                ..
+            } => {
+                self.update(shooter, target, *day, |interactions| {
+                    interactions.shots += 1;
+                    interactions.kills += *destroyed as u64;
+                });
+                self.detect_betrayal(*id, *day, shooter, target);
+            }
+            PlayerEvent::DonatePoints {
+                id,
+                day,
+                from,
+                to,
+                amount,
+                ..
+            } => {
+                self.update(from, to, *day, |interactions| {
+                    interactions.donations += 1;
+                    interactions.points_donated += *amount as u64;
+                });
+                self.donations.insert((from.clone(), to.clone()), *id);
+            }
+            _ => (),
+        }
+    }
+
+    fn update(&mut self, from: &str, to: &str, day: u8, change: impl Fn(&mut Interactions)) {
+        let edge = self
+            .edges
+            .entry((from.to_string(), to.to_string()))
+            .or_insert_with(|| Edge {
+                from: from.to_string(),
+                to: to.to_string(),
+                total: Interactions::default(),
+                days: BTreeMap::new(),
+            });
+        tally(&mut edge.total, &mut edge.days, day, change);
+    }
+
+    // A betrayal is only flagged once for every donation, otherwise each shot of a war would be
+    // reported again
+    fn detect_betrayal(&mut self, id: u64, day: u8, shooter: &str, target: &str) {
+        let pair = (shooter.to_string(), target.to_string());
+        let donated = self.donations.get(&pair).copied();
+        let funded = self
+            .donations
+            .get(&(target.to_string(), shooter.to_string()))
+            .copied();
+        let (donation, was_funded) = match (donated, funded) {
+            (None, None) => return,
+            (Some(donated), Some(funded)) if funded > donated => (funded, true),
+            (Some(donated), _) => (donated, false),
+            (None, Some(funded)) => (funded, true),
+        };
+        if self.flagged.get(&pair).is_some_and(|&last| last > donation) {
+            return;
+        }
+        self.flagged.insert(pair, id);
+        self.betrayals.push(Betrayal {
+            traitor: shooter.to_string(),
+            victim: target.to_string(),
+            event: id,
+            day,
+            donation,
+            was_funded,
+        });
+    }
+
+    pub fn edges(&self) -> impl Iterator<Item = &Edge> {
+        self.edges.values()
+    }
+
+    /// Betrayals in the order they happened
+    pub fn betrayals(&self) -> &Vec<Betrayal> {
+        &self.betrayals
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Renders the graph in the Graphviz DOT language. Shots are red, donations green and edges
+    /// with a betrayal are drawn bold.
+    pub fn to_dot<'a>(&self, players: impl Iterator<Item = &'a String>) -> String {
+        let mut dot = String::new();
+        self.write_dot(&mut dot, players).unwrap();
+        dot
+    }
+
+    fn write_dot<'a>(
+        &self,
+        dot: &mut String,
+        players: impl Iterator<Item = &'a String>,
+    ) -> fmt::Result {
+        writeln!(dot, "digraph woop {{")?;
+        for player in players {
+            writeln!(dot, "    {};", quote(player))?;
+        }
+        for edge in self.edges() {
+            let (from, to) = (quote(&edge.from), quote(&edge.to));
+            let total = &edge.total;
+            if total.shots > 0 {
+                let betrayed = self
+                    .flagged
+                    .contains_key(&(edge.from.clone(), edge.to.clone()));
+                writeln!(
+                    dot,
+                    "    {} -> {} [color=red, label=\"{} shots, {} kills\"{}];",
+                    from,
+                    to,
+                    total.shots,
+                    total.kills,
+                    if betrayed { ", style=bold" } else { "" }
+                )?;
+            }
+            if total.donations > 0 {
+                writeln!(
+                    dot,
+                    "    {} -> {} [color=green, label=\"{} points\"];",
+                    from, to, total.points_donated
+                )?;
+            }
+        }
+        writeln!(dot, "}}")
+    }
+}
+
+fn quote(id: &str) -> String {
+    format!("\"{}\"", id.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::InteractionGraph;
+    use crate::game::fixtures::{donate, hit, shoot};
+
+    #[test]
+    fn flag_betrayals() {
+        let mut graph = InteractionGraph::default();
+        graph.record(&shoot(0, 1, "mroik", "fin", hit()));
+        graph.record(&donate(1, "fin", "mroik", 10));
+        graph.record(&shoot(2, 1, "mroik", "fin", hit()));
+        graph.record(&shoot(3, 1, "mroik", "fin", hit()));
+        graph.record(&donate(4, "mroik", "fin", 10));
+        graph.record(&shoot(5, 1, "mroik", "fin", hit()));
+        graph.record(&shoot(6, 1, "warden", "fin", hit()));
+
+        let betrayals: Vec<(u64, u64, bool)> = graph
+            .betrayals()
+            .iter()
+            .map(|b| (b.event, b.donation, b.was_funded))
+            .collect();
+        assert_eq!(betrayals, vec![(2, 1, true), (5, 4, false)]);
+
+        let edge = graph.edges().find(|e| e.from == "mroik").unwrap();
+        assert_eq!(edge.total.shots, 4);
+        assert_eq!(edge.total.points_donated, 10);
+        assert_eq!(graph.edges().count(), 3);
+    }
+
+    #[test]
+    fn render_dot() {
+        let mut graph = InteractionGraph::default();
+        graph.record(&donate(0, "fin", "mro\"ik", 10));
+        let players = [String::from("fin"), String::from("mro\"ik")];
+        let dot = graph.to_dot(players.iter());
+        assert!(dot.contains("\"mro\\\"ik\";"));
+        assert!(dot.contains("\"fin\" -> \"mro\\\"ik\" [color=green, label=\"10 points\"];"));
+    }
+}
@@ -0,0 +1,188 @@
+use super::log::PlayerEvent;
+use serde::Deserialize;
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, ErrorKind, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+/// When the events appended to the journal are forced to disk
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FsyncPolicy {
+    /// After every event
+    Always,
+    /// Every time the game state is persisted, at rollover and on shutdown
+    #[default]
+    Periodic,
+    /// Whenever the OS feels like it
+    Never,
+}
+
+/// Append-only JSON Lines files holding every logged event, one file per game day
+#[derive(Debug)]
+pub struct Journal {
+    dir: PathBuf,
+    fsync: FsyncPolicy,
+    file: File,
+    /// First append that failed since the last sync, reported by the next one
+    error: Mutex<Option<String>>,
+    /// A failed write may have left half a line at the end of the file
+    torn: bool,
+}
+
+fn day_path(dir: &Path, day: u8) -> PathBuf {
+    dir.join(format!("day-{:02}.jsonl", day))
+}
+
+fn open_day(dir: &Path, day: u8) -> io::Result<File> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(day_path(dir, day))
+}
+
+impl Journal {
+    pub fn open(dir: &Path, fsync: FsyncPolicy, day: u8) -> io::Result<Journal> {
+        fs::create_dir_all(dir)?;
+        Ok(Journal {
+            dir: dir.to_path_buf(),
+            fsync,
+            file: open_day(dir, day)?,
+            error: Mutex::new(None),
+            torn: false,
+        })
+    }
+
+    pub fn append(&mut self, event: &PlayerEvent) {
+        // Ending the torn line first keeps it from swallowing this event, it's then skipped as
+        // malformed when read back
+        let mut line = if self.torn { vec![b'\n'] } else { Vec::new() };
+        serde_json::to_writer(&mut line, event).unwrap();
+        line.push(b'\n');
+        let mut result = self.file.write_all(line.as_slice());
+        self.torn = result.is_err();
+        if result.is_ok() && self.fsync == FsyncPolicy::Always {
+            result = self.file.sync_data();
+        }
+        if let Err(err) = result {
+            log::error!("Couldn't append event to {}: {}", self.dir.display(), err);
+            self.error
+                .lock()
+                .unwrap()
+                .get_or_insert_with(|| err.to_string());
+        }
+    }
+
+    /// Moves on to the file of `day`, the file of the previous day is synced before leaving it
+    pub fn rotate(&mut self, day: u8) -> io::Result<()> {
+        if self.fsync != FsyncPolicy::Never {
+            self.file.sync_data()?;
+        }
+        self.file = open_day(self.dir.as_path(), day)?;
+        Ok(())
+    }
+
+    /// Forces the events to disk according to the policy, failing if an append failed since the
+    /// last sync
+    pub fn sync(&self) -> io::Result<()> {
+        if let Some(err) = self.error.lock().unwrap().take() {
+            return Err(io::Error::other(err));
+        }
+        match self.fsync {
+            FsyncPolicy::Never => Ok(()),
+            _ => self.file.sync_data(),
+        }
+    }
+
+    /// Days for which there's a file, in ascending order
+    pub fn days(&self) -> io::Result<Vec<u8>> {
+        let mut days: Vec<u8> = fs::read_dir(self.dir.as_path())?
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let name = entry.file_name().into_string().ok()?;
+                name.strip_prefix("day-")?
+                    .strip_suffix(".jsonl")?
+                    .parse()
+                    .ok()
+            })
+            .collect();
+        days.sort();
+        Ok(days)
+    }
+
+    /// Events logged during `day`, oldest first. A line cut short by a crash is skipped.
+    pub fn read_day(&self, day: u8) -> io::Result<Vec<PlayerEvent>> {
+        let file = match File::open(day_path(self.dir.as_path(), day)) {
+            Ok(file) => file,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err),
+        };
+        let mut events = Vec::new();
+        for line in BufReader::new(file).lines() {
+            match serde_json::from_str(line?.as_str()) {
+                Ok(event) => events.push(event),
+                Err(err) => log::warn!("Skipping malformed event in day {}: {}", day, err),
+            }
+        }
+        Ok(events)
+    }
+
+    // Errors are logged and skipped, a page with a hole is more useful than no page at all
+    fn read_day_or_log(&self, day: u8) -> Vec<PlayerEvent> {
+        self.read_day(day).unwrap_or_else(|err| {
+            log::error!("Couldn't read day {}: {}", day, err);
+            Vec::new()
+        })
+    }
+
+    fn days_or_log(&self) -> Vec<u8> {
+        self.days().unwrap_or_else(|err| {
+            log::error!("Couldn't list {}: {}", self.dir.display(), err);
+            Vec::new()
+        })
+    }
+
+    /// Every event in the journal, newest first. Files are only read once they are reached.
+    pub fn newest_first(&self) -> impl Iterator<Item = PlayerEvent> + '_ {
+        self.days_or_log()
+            .into_iter()
+            .rev()
+            .flat_map(move |day| self.read_day_or_log(day).into_iter().rev())
+    }
+
+    /// Every event in the journal from `from` on, oldest first. Files are only read once they
+    /// are reached, the ones of the days before `from` not at all.
+    pub fn oldest_first(&self, from: u8) -> impl Iterator<Item = PlayerEvent> + '_ {
+        self.days_or_log()
+            .into_iter()
+            .filter(move |day| *day >= from)
+            .flat_map(move |day| self.read_day_or_log(day))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{day_path, FsyncPolicy, Journal};
+    use crate::game::fixtures::shield;
+    use std::{env, fs, fs::File};
+
+    #[test]
+    fn failed_append_is_reported() {
+        let dir = env::temp_dir().join(format!("woop-journal-append-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let mut journal = Journal::open(&dir, FsyncPolicy::Never, 1).unwrap();
+
+        // A read only handle fails every write
+        let writable = std::mem::replace(&mut journal.file, File::open(day_path(&dir, 1)).unwrap());
+        journal.append(&shield(0, "mroik", (0, 0)));
+        journal.file = writable;
+        journal.append(&shield(1, "mroik", (0, 0)));
+
+        assert!(journal.sync().is_err());
+        assert!(journal.sync().is_ok());
+        assert_eq!(journal.read_day(1).unwrap().len(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,158 +1,1096 @@
-use serde::Serialize;
-use std::time::UNIX_EPOCH;
-use utoipa::ToSchema;
+use super::{
+    clock::Clock,
+    error::WoopError,
+    feed::{Feed, Live},
+    graph::InteractionGraph,
+    journal::{FsyncPolicy, Journal},
+    stats::Stats,
+    treaty::{Treaty, TreatyStatus},
+};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::{
+    collections::{BTreeMap, VecDeque},
+    io,
+    path::Path,
+};
+use utoipa::{IntoParams, ToSchema};
 
-const ACTIVITY_CHUNK_SIZE: usize = 100;
+const DEFAULT_PAGE_SIZE: usize = 100;
+const MAX_PAGE_SIZE: usize = 500;
+// Events kept in memory when there's a journal to read older ones from
+const MEMORY_WINDOW: usize = 1000;
 
-#[derive(Debug, Serialize, Clone, ToSchema)]
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum PlayerEvent {
     Shoot {
+        id: u64,
+        day: u8,
         shooter: String,
         from: (i16, i16),
         to: (i16, i16),
         target: String,
+        /// The hit was absorbed by a shield
+        #[serde(default)]
+        shielded: bool,
+        /// Hp of the target zord after the hit
+        #[serde(default)]
+        hp_left: u8,
+        /// The target zord was destroyed
+        #[serde(default)]
+        destroyed: bool,
+        /// Points earned by the shooter
+        #[serde(default)]
+        reward: u16,
+        /// The target lost their last zord, followed by a `point_penalty` event
+        #[serde(default)]
+        wiped_out: bool,
         timestamp: u64,
     },
     Move {
+        id: u64,
+        day: u8,
         player: String,
         from: (i16, i16),
         to: (i16, i16),
         timestamp: u64,
     },
     GenerateShield {
+        id: u64,
+        day: u8,
         player: String,
         zord_coord: (i16, i16),
         timestamp: u64,
     },
     IncreaseRange {
+        id: u64,
+        day: u8,
         player: String,
         zord_coord: (i16, i16),
         timestamp: u64,
     },
     DonatePoints {
+        id: u64,
+        day: u8,
         from: String,
         to: String,
+        #[serde(default)]
+        amount: u16,
         timestamp: u64,
     },
     BuildZord {
+        id: u64,
+        day: u8,
         player: String,
         zord_coord: (i16, i16),
+        /// Points paid for the zord
+        #[serde(default)]
+        cost: u16,
         timestamp: u64,
     },
     TotemPoints {
+        id: u64,
+        day: u8,
         player: String,
         coord: (i16, i16),
         points: u16,
         timestamp: u64,
     },
     Respawn {
+        id: u64,
+        day: u8,
         player: String,
         coord: (i16, i16),
         timestamp: u64,
     },
     TotemSpawned {
+        id: u64,
+        day: u8,
         coord: (i16, i16),
         timestamp: u64,
     },
+    /// Shields left on a zord are removed at rollover
+    ShieldExpired {
+        id: u64,
+        day: u8,
+        player: String,
+        zord_coord: (i16, i16),
+        shields: u8,
+        timestamp: u64,
+    },
+    /// A zord with increased range is brought back to the base range at rollover
+    RangeReset {
+        id: u64,
+        day: u8,
+        player: String,
+        zord_coord: (i16, i16),
+        /// Range before the reset
+        range: u8,
+        timestamp: u64,
+    },
+    /// Points taken away from a player
+    PointPenalty {
+        id: u64,
+        day: u8,
+        player: String,
+        /// Points lost
+        points: u16,
+        /// Points left after the penalty
+        points_left: u16,
+        timestamp: u64,
+    },
+    TeamCreated {
+        id: u64,
+        day: u8,
+        team: String,
+        player: String,
+        timestamp: u64,
+    },
+    TeamInvite {
+        id: u64,
+        day: u8,
+        team: String,
+        from: String,
+        to: String,
+        timestamp: u64,
+    },
+    TeamJoined {
+        id: u64,
+        day: u8,
+        team: String,
+        player: String,
+        timestamp: u64,
+    },
+    /// The team is disbanded when the last member leaves
+    TeamLeft {
+        id: u64,
+        day: u8,
+        team: String,
+        player: String,
+        timestamp: u64,
+    },
+    /// A treaty was proposed or changed status
+    TreatyUpdate {
+        id: u64,
+        day: u8,
+        treaty: u64,
+        proposer: String,
+        counterpart: String,
+        status: TreatyStatus,
+        timestamp: u64,
+    },
+    /// Points moved by a treaty, between players or in and out of escrow
+    TreatyPoints {
+        id: u64,
+        day: u8,
+        treaty: u64,
+        /// Nobody when the points come out of escrow
+        from: Option<String>,
+        /// Nobody when the points go into escrow
+        to: Option<String>,
+        amount: u16,
+        timestamp: u64,
+    },
+}
+
+/// What a shot did to its target
+pub struct ShotOutcome {
+    pub shielded: bool,
+    pub hp_left: u8,
+    pub destroyed: bool,
+    pub reward: u16,
+    pub wiped_out: bool,
 }
 
-#[derive(Debug)]
+impl PlayerEvent {
+    /// Name of the variant, matches the serialized tag
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Shoot { .. } => "shoot",
+            Self::Move { .. } => "move",
+            Self::GenerateShield { .. } => "generate_shield",
+            Self::IncreaseRange { .. } => "increase_range",
+            Self::DonatePoints { .. } => "donate_points",
+            Self::BuildZord { .. } => "build_zord",
+            Self::TotemPoints { .. } => "totem_points",
+            Self::Respawn { .. } => "respawn",
+            Self::TotemSpawned { .. } => "totem_spawned",
+            Self::ShieldExpired { .. } => "shield_expired",
+            Self::RangeReset { .. } => "range_reset",
+            Self::PointPenalty { .. } => "point_penalty",
+            Self::TeamCreated { .. } => "team_created",
+            Self::TeamInvite { .. } => "team_invite",
+            Self::TeamJoined { .. } => "team_joined",
+            Self::TeamLeft { .. } => "team_left",
+            Self::TreatyUpdate { .. } => "treaty_update",
+            Self::TreatyPoints { .. } => "treaty_points",
+        }
+    }
+
+    pub fn id(&self) -> u64 {
+        match self {
+            Self::Shoot { id, .. }
+            | Self::Move { id, .. }
+            | Self::GenerateShield { id, .. }
+            | Self::IncreaseRange { id, .. }
+            | Self::DonatePoints { id, .. }
+            | Self::BuildZord { id, .. }
+            | Self::TotemPoints { id, .. }
+            | Self::Respawn { id, .. }
+            | Self::TotemSpawned { id, .. }
+            | Self::ShieldExpired { id, .. }
+            | Self::RangeReset { id, .. }
+            | Self::PointPenalty { id, .. }
+            | Self::TeamCreated { id, .. }
+            | Self::TeamInvite { id, .. }
+            | Self::TeamJoined { id, .. }
+            | Self::TeamLeft { id, .. }
+            | Self::TreatyUpdate { id, .. }
+            | Self::TreatyPoints { id, .. } => *id,
+        }
+    }
+
+    pub fn day(&self) -> u8 {
+        match self {
+            Self::Shoot { day, .. }
+            | Self::Move { day, .. }
+            | Self::GenerateShield { day, .. }
+            | Self::IncreaseRange { day, .. }
+            | Self::DonatePoints { day, .. }
+            | Self::BuildZord { day, .. }
+            | Self::TotemPoints { day, .. }
+            | Self::Respawn { day, .. }
+            | Self::TotemSpawned { day, .. }
+            | Self::ShieldExpired { day, .. }
+            | Self::RangeReset { day, .. }
+            | Self::PointPenalty { day, .. }
+            | Self::TeamCreated { day, .. }
+            | Self::TeamInvite { day, .. }
+            | Self::TeamJoined { day, .. }
+            | Self::TeamLeft { day, .. }
+            | Self::TreatyUpdate { day, .. }
+            | Self::TreatyPoints { day, .. } => *day,
+        }
+    }
+
+    pub fn timestamp(&self) -> u64 {
+        match self {
+            Self::Shoot { timestamp, .. }
+            | Self::Move { timestamp, .. }
+            | Self::GenerateShield { timestamp, .. }
+            | Self::IncreaseRange { timestamp, .. }
+            | Self::DonatePoints { timestamp, .. }
+            | Self::BuildZord { timestamp, .. }
+            | Self::TotemPoints { timestamp, .. }
+            | Self::Respawn { timestamp, .. }
+            | Self::TotemSpawned { timestamp, .. }
+            | Self::ShieldExpired { timestamp, .. }
+            | Self::RangeReset { timestamp, .. }
+            | Self::PointPenalty { timestamp, .. }
+            | Self::TeamCreated { timestamp, .. }
+            | Self::TeamInvite { timestamp, .. }
+            | Self::TeamJoined { timestamp, .. }
+            | Self::TeamLeft { timestamp, .. }
+            | Self::TreatyUpdate { timestamp, .. }
+            | Self::TreatyPoints { timestamp, .. } => *timestamp,
+        }
+    }
+
+    /// Players involved in the event
+    pub fn players(&self) -> Vec<&str> {
+        match self {
+            Self::Shoot {
+                shooter, target, ..
+            } => vec![shooter, target],
+            Self::DonatePoints { from, to, .. } | Self::TeamInvite { from, to, .. } => {
+                vec![from, to]
+            }
+            Self::Move { player, .. }
+            | Self::GenerateShield { player, .. }
+            | Self::IncreaseRange { player, .. }
+            | Self::BuildZord { player, .. }
+            | Self::TotemPoints { player, .. }
+            | Self::Respawn { player, .. }
+            | Self::ShieldExpired { player, .. }
+            | Self::RangeReset { player, .. }
+            | Self::PointPenalty { player, .. }
+            | Self::TeamCreated { player, .. }
+            | Self::TeamJoined { player, .. }
+            | Self::TeamLeft { player, .. } => vec![player],
+            Self::TreatyUpdate {
+                proposer,
+                counterpart,
+                ..
+            } => vec![proposer, counterpart],
+            Self::TreatyPoints { from, to, .. } => {
+                from.iter().chain(to.iter()).map(String::as_str).collect()
+            }
+            Self::TotemSpawned { .. } => vec![],
+        }
+    }
+
+    /// Cells of the board involved in the event
+    pub fn coords(&self) -> Vec<(i16, i16)> {
+        match self {
+            Self::Shoot { from, to, .. } | Self::Move { from, to, .. } => vec![*from, *to],
+            Self::GenerateShield { zord_coord, .. }
+            | Self::IncreaseRange { zord_coord, .. }
+            | Self::BuildZord { zord_coord, .. }
+            | Self::ShieldExpired { zord_coord, .. }
+            | Self::RangeReset { zord_coord, .. } => vec![*zord_coord],
+            Self::TotemPoints { coord, .. }
+            | Self::Respawn { coord, .. }
+            | Self::TotemSpawned { coord, .. } => vec![*coord],
+            Self::DonatePoints { .. }
+            | Self::PointPenalty { .. }
+            | Self::TeamCreated { .. }
+            | Self::TeamInvite { .. }
+            | Self::TeamJoined { .. }
+            | Self::TeamLeft { .. }
+            | Self::TreatyUpdate { .. }
+            | Self::TreatyPoints { .. } => vec![],
+        }
+    }
+
+    /// Alliance and treaty changes are seen by everyone, even when the game limits the vision
+    pub fn public(&self) -> bool {
+        matches!(
+            self,
+            Self::TeamCreated { .. }
+                | Self::TeamInvite { .. }
+                | Self::TeamJoined { .. }
+                | Self::TeamLeft { .. }
+                | Self::TreatyUpdate { .. }
+                | Self::TreatyPoints { .. }
+        )
+    }
+}
+
+/// Filters and cursor of an activity query. Events are matched by every filter that is set.
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct EventQuery {
+    /// Only return events older than this event id
+    pub before: Option<u64>,
+    /// Only return events newer than this event id
+    pub after: Option<u64>,
+    /// Maximum amount of events returned, capped at 500
+    pub limit: Option<usize>,
+    /// Only return events involving this player
+    pub player: Option<String>,
+    /// Comma separated list of event kinds, e.g. `shoot,move`
+    pub kind: Option<String>,
+    /// Only return events of this game day
+    pub day: Option<u8>,
+    /// Only return events logged at or after this unix timestamp
+    pub since: Option<u64>,
+    /// Only return events logged at or before this unix timestamp
+    pub until: Option<u64>,
+    /// Only return events involving a cell with x greater or equal to this
+    pub x_min: Option<i16>,
+    /// Only return events involving a cell with x less or equal to this
+    pub x_max: Option<i16>,
+    /// Only return events involving a cell with y greater or equal to this
+    pub y_min: Option<i16>,
+    /// Only return events involving a cell with y less or equal to this
+    pub y_max: Option<i16>,
+    /// No longer supported, a query with it is rejected instead of quietly returning the newest
+    /// page
+    pub chunk: Option<usize>,
+}
+
+impl EventQuery {
+    pub fn check(&self) -> Result<(), WoopError> {
+        match self.chunk {
+            Some(_) => Err(WoopError::ChunkRemoved),
+            None => Ok(()),
+        }
+    }
+
+    fn limit(&self) -> usize {
+        self.limit
+            .unwrap_or(DEFAULT_PAGE_SIZE)
+            .clamp(1, MAX_PAGE_SIZE)
+    }
+
+    fn has_region(&self) -> bool {
+        self.x_min.is_some() || self.x_max.is_some() || self.y_min.is_some() || self.y_max.is_some()
+    }
+
+    fn in_region(&self, (x, y): (i16, i16)) -> bool {
+        self.x_min.is_none_or(|min| x >= min)
+            && self.x_max.is_none_or(|max| x <= max)
+            && self.y_min.is_none_or(|min| y >= min)
+            && self.y_max.is_none_or(|max| y <= max)
+    }
+
+    pub fn matches(&self, event: &PlayerEvent) -> bool {
+        let id = event.id();
+        self.before.is_none_or(|before| id < before)
+            && self.after.is_none_or(|after| id > after)
+            && self.day.is_none_or(|day| event.day() == day)
+            && self.since.is_none_or(|since| event.timestamp() >= since)
+            && self.until.is_none_or(|until| event.timestamp() <= until)
+            && self
+                .kind
+                .as_deref()
+                .is_none_or(|kinds| kinds.split(',').any(|kind| kind.trim() == event.kind()))
+            && self
+                .player
+                .as_deref()
+                .is_none_or(|player| event.players().contains(&player))
+            && (!self.has_region() || event.coords().into_iter().any(|c| self.in_region(c)))
+    }
+}
+
+/// A page of events, newest first
+pub struct EventPage {
+    pub events: Vec<PlayerEvent>,
+    /// Cursor to pass as `before` to get the page of older events
+    pub before: Option<u64>,
+    /// Cursor to pass as `after` to get the page of newer events
+    pub after: Option<u64>,
+}
+
+// Without a journal every event is kept in memory, otherwise only the most recent ones are and
+// the rest is read back from the journal when needed
+#[derive(Debug, Default)]
 pub struct Logger {
-    data: Vec<PlayerEvent>,
+    data: VecDeque<PlayerEvent>,
+    counts: BTreeMap<&'static str, u64>,
+    stats: Stats,
+    graph: InteractionGraph,
+    next_id: u64,
+    day: u8,
+    /// Id of the first event of each day
+    first_ids: BTreeMap<u8, u64>,
+    journal: Option<Journal>,
+    clock: Clock,
+    feed: Feed,
 }
 
 impl Logger {
-    pub fn shoot(&mut self, shooter: &str, from: (i16, i16), to: (i16, i16), target: &str) {
-        self.data.push(PlayerEvent::Shoot {
+    fn push(&mut self, build: impl FnOnce(u64, u8, u64) -> PlayerEvent) {
+        let event = build(self.next_id, self.day, self.clock.unix_timestamp());
+        if let Some(journal) = self.journal.as_mut() {
+            journal.append(&event);
+        }
+        self.feed.publish(|| Live::Event(event.clone()));
+        self.record(event);
+    }
+
+    fn record(&mut self, event: PlayerEvent) {
+        *self.counts.entry(event.kind()).or_insert(0) += 1;
+        self.stats.record(&event);
+        self.graph.record(&event);
+        self.next_id = self.next_id.max(event.id() + 1);
+        self.first_ids.entry(event.day()).or_insert(event.id());
+        self.data.push_back(event);
+        if self.journal.is_some() && self.data.len() > MEMORY_WINDOW {
+            self.data.pop_front();
+        }
+    }
+
+    pub fn shoot(
+        &mut self,
+        shooter: &str,
+        from: (i16, i16),
+        to: (i16, i16),
+        target: &str,
+        outcome: ShotOutcome,
+    ) {
+        self.push(|id, day, timestamp| PlayerEvent::Shoot {
+            id,
+            day,
             shooter: shooter.to_string(),
             from,
             to,
             target: target.to_string(),
-            timestamp: unix_timestamp(),
+            shielded: outcome.shielded,
+            hp_left: outcome.hp_left,
+            destroyed: outcome.destroyed,
+            reward: outcome.reward,
+            wiped_out: outcome.wiped_out,
+            timestamp,
         });
     }
 
     pub fn move_zord(&mut self, player: &str, from: (i16, i16), to: (i16, i16)) {
-        self.data.push(PlayerEvent::Move {
+        self.push(|id, day, timestamp| PlayerEvent::Move {
+            id,
+            day,
             player: player.to_string(),
             from,
             to,
-            timestamp: unix_timestamp(),
+            timestamp,
         });
     }
 
     pub fn generate_shield(&mut self, player: &str, zord_coord: (i16, i16)) {
-        self.data.push(PlayerEvent::GenerateShield {
+        self.push(|id, day, timestamp| PlayerEvent::GenerateShield {
+            id,
+            day,
             player: player.to_string(),
             zord_coord,
-            timestamp: unix_timestamp(),
+            timestamp,
         });
     }
 
     pub fn increase_range(&mut self, player: &str, zord_coord: (i16, i16)) {
-        self.data.push(PlayerEvent::IncreaseRange {
+        self.push(|id, day, timestamp| PlayerEvent::IncreaseRange {
+            id,
+            day,
             player: player.to_string(),
             zord_coord,
-            timestamp: unix_timestamp(),
+            timestamp,
         });
     }
 
-    pub fn donate_points(&mut self, from: &str, to: &str) {
-        self.data.push(PlayerEvent::DonatePoints {
+    pub fn donate_points(&mut self, from: &str, to: &str, amount: u16) {
+        self.push(|id, day, timestamp| PlayerEvent::DonatePoints {
+            id,
+            day,
             from: from.to_string(),
             to: to.to_string(),
-            timestamp: unix_timestamp(),
+            amount,
+            timestamp,
         });
     }
 
-    pub fn build_zord(&mut self, player: &str, zord_coord: (i16, i16)) {
-        self.data.push(PlayerEvent::BuildZord {
+    pub fn build_zord(&mut self, player: &str, zord_coord: (i16, i16), cost: u16) {
+        self.push(|id, day, timestamp| PlayerEvent::BuildZord {
+            id,
+            day,
             player: player.to_string(),
             zord_coord,
-            timestamp: unix_timestamp(),
+            cost,
+            timestamp,
         });
     }
 
     pub fn totem_points(&mut self, player: &str, coord: (i16, i16), points: u16) {
-        self.data.push(PlayerEvent::TotemPoints {
+        self.push(|id, day, timestamp| PlayerEvent::TotemPoints {
+            id,
+            day,
             player: player.to_string(),
             coord,
             points,
-            timestamp: unix_timestamp(),
+            timestamp,
         });
     }
 
     pub fn respawn(&mut self, player: &str, coord: (i16, i16)) {
-        self.data.push(PlayerEvent::Respawn {
+        self.push(|id, day, timestamp| PlayerEvent::Respawn {
+            id,
+            day,
             player: player.to_string(),
             coord,
-            timestamp: unix_timestamp(),
+            timestamp,
         });
     }
 
     pub fn totem_spawned(&mut self, coord: (i16, i16)) {
-        self.data.push(PlayerEvent::TotemSpawned {
+        self.push(|id, day, timestamp| PlayerEvent::TotemSpawned {
+            id,
+            day,
             coord,
-            timestamp: unix_timestamp(),
+            timestamp,
+        });
+    }
+
+    pub fn shield_expired(&mut self, player: &str, zord_coord: (i16, i16), shields: u8) {
+        self.push(|id, day, timestamp| PlayerEvent::ShieldExpired {
+            id,
+            day,
+            player: player.to_string(),
+            zord_coord,
+            shields,
+            timestamp,
+        });
+    }
+
+    pub fn range_reset(&mut self, player: &str, zord_coord: (i16, i16), range: u8) {
+        self.push(|id, day, timestamp| PlayerEvent::RangeReset {
+            id,
+            day,
+            player: player.to_string(),
+            zord_coord,
+            range,
+            timestamp,
+        });
+    }
+
+    pub fn point_penalty(&mut self, player: &str, points: u16, points_left: u16) {
+        self.push(|id, day, timestamp| PlayerEvent::PointPenalty {
+            id,
+            day,
+            player: player.to_string(),
+            points,
+            points_left,
+            timestamp,
+        });
+    }
+
+    pub fn team_created(&mut self, team: &str, player: &str) {
+        self.push(|id, day, timestamp| PlayerEvent::TeamCreated {
+            id,
+            day,
+            team: team.to_string(),
+            player: player.to_string(),
+            timestamp,
+        });
+    }
+
+    pub fn team_invite(&mut self, team: &str, from: &str, to: &str) {
+        self.push(|id, day, timestamp| PlayerEvent::TeamInvite {
+            id,
+            day,
+            team: team.to_string(),
+            from: from.to_string(),
+            to: to.to_string(),
+            timestamp,
+        });
+    }
+
+    pub fn team_joined(&mut self, team: &str, player: &str) {
+        self.push(|id, day, timestamp| PlayerEvent::TeamJoined {
+            id,
+            day,
+            team: team.to_string(),
+            player: player.to_string(),
+            timestamp,
+        });
+    }
+
+    pub fn team_left(&mut self, team: &str, player: &str) {
+        self.push(|id, day, timestamp| PlayerEvent::TeamLeft {
+            id,
+            day,
+            team: team.to_string(),
+            player: player.to_string(),
+            timestamp,
+        });
+    }
+
+    pub fn treaty_update(&mut self, treaty: &Treaty) {
+        self.push(|id, day, timestamp| PlayerEvent::TreatyUpdate {
+            id,
+            day,
+            treaty: treaty.id,
+            proposer: treaty.proposer.clone(),
+            counterpart: treaty.counterpart.clone(),
+            status: treaty.status.clone(),
+            timestamp,
+        });
+    }
+
+    pub fn treaty_points(
+        &mut self,
+        treaty: u64,
+        from: Option<&str>,
+        to: Option<&str>,
+        amount: u16,
+    ) {
+        self.push(|id, day, timestamp| PlayerEvent::TreatyPoints {
+            id,
+            day,
+            treaty,
+            from: from.map(String::from),
+            to: to.map(String::from),
+            amount,
+            timestamp,
         });
     }
 
     pub fn new() -> Logger {
-        Self { data: Vec::new() }
+        Self::default()
+    }
+
+    /// Starts appending events to the journal in `dir`. The journal is the source of truth, so
+    /// the events held in memory are replaced with the ones read from it.
+    pub fn attach_journal(&mut self, dir: &Path, fsync: FsyncPolicy) -> io::Result<()> {
+        let journal = Journal::open(dir, fsync, self.day)?;
+        self.data.clear();
+        self.counts.clear();
+        self.stats.clear();
+        self.graph.clear();
+        self.first_ids.clear();
+        self.journal = Some(journal);
+        let journal = self.journal.as_ref().unwrap();
+        let mut events = Vec::new();
+        for day in journal.days()? {
+            events.extend(journal.read_day(day)?);
+        }
+        events.into_iter().for_each(|event| self.record(event));
+        Ok(())
+    }
+
+    /// An empty logger without a journal that numbers events from where this one is at, so that
+    /// what gets logged to it can be thrown away
+    pub fn detached(&self) -> Logger {
+        Logger {
+            next_id: self.next_id,
+            day: self.day,
+            clock: self.clock.clone(),
+            ..Self::default()
+        }
+    }
+
+    /// Where new events are published as they are logged
+    pub fn feed(&self) -> &Feed {
+        &self.feed
+    }
+
+    /// Clock the events are timestamped with
+    pub fn set_clock(&mut self, clock: Clock) {
+        self.clock = clock;
+    }
+
+    pub fn new_day(&mut self, day: u8) {
+        self.day = day;
+        if let Some(journal) = self.journal.as_mut() {
+            if let Err(err) = journal.rotate(day) {
+                log::error!("Couldn't rotate journal to day {}: {}", day, err);
+            }
+        }
+    }
+
+    /// Makes sure the journal is on disk, this is a no-op without a journal
+    pub fn sync(&self) -> io::Result<()> {
+        match &self.journal {
+            Some(journal) => journal.sync(),
+            None => Ok(()),
+        }
+    }
+
+    /// Number of events logged so far for each kind of event
+    pub fn counts(&self) -> &BTreeMap<&'static str, u64> {
+        &self.counts
+    }
+
+    /// Per player counters over every event logged so far
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    /// Who shot and who funded whom over every event logged so far
+    pub fn graph(&self) -> &InteractionGraph {
+        &self.graph
+    }
+
+    // Events older than the ones in memory can only be found in the journal
+    fn journal_only(&self) -> impl Iterator<Item = PlayerEvent> + '_ {
+        let oldest_in_memory = self.data.front().map(|event| event.id());
+        self.journal
+            .iter()
+            .flat_map(|journal| journal.newest_first())
+            .filter(move |event| oldest_in_memory.is_none_or(|oldest| event.id() < oldest))
+    }
+
+    /// Every event, newest first
+    pub fn newest_first(&self) -> impl Iterator<Item = PlayerEvent> + '_ {
+        self.data.iter().rev().cloned().chain(self.journal_only())
+    }
+
+    /// Every event, oldest first
+    pub fn oldest_first(&self) -> impl Iterator<Item = PlayerEvent> + '_ {
+        self.oldest_first_from(0)
+    }
+
+    /// Events logged during `day` and the days after, oldest first
+    pub fn oldest_first_from(&self, day: u8) -> impl Iterator<Item = PlayerEvent> + '_ {
+        let oldest_in_memory = self.data.front().map(|event| event.id());
+        self.journal
+            .iter()
+            .flat_map(move |journal| journal.oldest_first(day))
+            .take_while(move |event| oldest_in_memory.is_none_or(|oldest| event.id() < oldest))
+            .chain(
+                self.data
+                    .iter()
+                    .skip_while(move |event| event.day() < day)
+                    .cloned(),
+            )
     }
 
-    pub fn get_chunk(&self, chunk: usize) -> Vec<PlayerEvent> {
-        self.data
+    // Events newer than `after`, oldest first. A cursor within the memory window is served from
+    // memory alone, an older one only reads the journal from the day of the cursor on.
+    fn oldest_after(&self, after: u64) -> impl Iterator<Item = PlayerEvent> + '_ {
+        let in_memory = self.data.front().is_some_and(|oldest| after >= oldest.id());
+        let day = self
+            .first_ids
             .iter()
             .rev()
-            .skip(ACTIVITY_CHUNK_SIZE * chunk)
-            .take(ACTIVITY_CHUNK_SIZE)
-            .cloned()
-            .collect()
+            .find(|(_, first)| **first <= after)
+            .map_or(0, |(day, _)| *day);
+        let from_journal = (!in_memory)
+            .then(|| self.oldest_first_from(day))
+            .into_iter()
+            .flatten();
+        let from_memory = in_memory
+            .then(|| self.data.iter().cloned())
+            .into_iter()
+            .flatten();
+        from_journal
+            .chain(from_memory)
+            .skip_while(move |event| event.id() <= after)
+    }
+
+    /// Pages through the events matching `query`. With an `after` cursor the page holds the
+    /// events right after it, otherwise the newest events before the `before` cursor.
+    pub fn query(&self, query: &EventQuery) -> EventPage {
+        self.query_where(query, |_| true)
+    }
+
+    /// Same as `query`, leaving out the events `visible` rejects
+    pub fn query_where(
+        &self,
+        query: &EventQuery,
+        visible: impl Fn(&PlayerEvent) -> bool,
+    ) -> EventPage {
+        let matches = |event: &PlayerEvent| query.matches(event) && visible(event);
+        let limit = query.limit();
+        let events: Vec<PlayerEvent> = match query.after {
+            Some(after) => {
+                let mut events: Vec<PlayerEvent> = self
+                    .oldest_after(after)
+                    .filter(|event| matches(event))
+                    .take(limit)
+                    .collect();
+                events.reverse();
+                events
+            }
+            None => self
+                .newest_first()
+                .skip_while(|event| query.before.is_some_and(|before| event.id() >= before))
+                .filter(|event| matches(event))
+                .take(limit)
+                .collect(),
+        };
+        EventPage {
+            before: events.last().map(|event| event.id()),
+            after: events.first().map(|event| event.id()),
+            events,
+        }
     }
 }
 
-fn unix_timestamp() -> u64 {
-    UNIX_EPOCH.elapsed().unwrap().as_secs()
+#[derive(Serialize)]
+struct StoredLoggerRef<'a> {
+    next_id: u64,
+    day: u8,
+    events: &'a VecDeque<PlayerEvent>,
+}
+
+#[derive(Deserialize)]
+struct StoredLogger {
+    next_id: u64,
+    day: u8,
+    events: VecDeque<PlayerEvent>,
+}
+
+// The counters are rebuilt when loading the events back, while the journal has to be attached
+// again by whoever restores the logger
+impl Serialize for Logger {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        StoredLoggerRef {
+            next_id: self.next_id,
+            day: self.day,
+            events: &self.data,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Logger {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let stored = StoredLogger::deserialize(deserializer)?;
+        let mut logger = Logger::new();
+        logger.day = stored.day;
+        stored
+            .events
+            .into_iter()
+            .for_each(|event| logger.record(event));
+        logger.next_id = logger.next_id.max(stored.next_id);
+        Ok(logger)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EventQuery, Logger, ShotOutcome, MEMORY_WINDOW};
+    use crate::game::journal::FsyncPolicy;
+    use std::{env, fs};
+
+    fn ids(logger: &Logger, query: &EventQuery) -> Vec<u64> {
+        logger.query(query).events.iter().map(|e| e.id()).collect()
+    }
+
+    #[test]
+    fn ids_are_monotonic() {
+        let mut logger = Logger::new();
+        logger.respawn("mroik", (0, 0));
+        logger.move_zord("mroik", (0, 0), (1, 1));
+        assert_eq!(ids(&logger, &EventQuery::default()), vec![1, 0]);
+    }
+
+    #[test]
+    fn chunk_is_rejected() {
+        assert!(EventQuery::default().check().is_ok());
+        let query = EventQuery {
+            chunk: Some(0),
+            ..Default::default()
+        };
+        assert!(query.check().is_err());
+    }
+
+    #[test]
+    fn filter_and_cursor() {
+        let mut logger = Logger::new();
+        logger.respawn("mroik", (0, 0));
+        logger.move_zord("fin", (5, 5), (6, 5));
+        logger.shoot(
+            "mroik",
+            (0, 0),
+            (5, 5),
+            "fin",
+            ShotOutcome {
+                shielded: false,
+                hp_left: 1,
+                destroyed: false,
+                reward: 0,
+                wiped_out: false,
+            },
+        );
+        logger.move_zord("mroik", (0, 0), (1, 0));
+        logger.new_day(1);
+        logger.totem_spawned((9, 9));
+
+        let query = EventQuery {
+            player: Some(String::from("fin")),
+            ..Default::default()
+        };
+        assert_eq!(ids(&logger, &query), vec![2, 1]);
+
+        let query = EventQuery {
+            kind: Some(String::from("move,totem_spawned")),
+            day: Some(0),
+            ..Default::default()
+        };
+        assert_eq!(ids(&logger, &query), vec![3, 1]);
+
+        let query = EventQuery {
+            x_min: Some(5),
+            y_min: Some(5),
+            ..Default::default()
+        };
+        assert_eq!(ids(&logger, &query), vec![4, 2, 1]);
+
+        let page = logger.query(&EventQuery {
+            limit: Some(2),
+            ..Default::default()
+        });
+        assert_eq!((page.after, page.before), (Some(4), Some(3)));
+        let query = EventQuery {
+            before: page.before,
+            limit: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(ids(&logger, &query), vec![2, 1]);
+        let query = EventQuery {
+            after: Some(0),
+            limit: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(ids(&logger, &query), vec![2, 1]);
+    }
+
+    #[test]
+    fn page_through_journal() {
+        let dir = env::temp_dir().join(format!("woop-journal-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+
+        let mut logger = Logger::new();
+        logger.attach_journal(&dir, FsyncPolicy::Never).unwrap();
+        logger.totem_spawned((0, 0));
+        logger.new_day(1);
+        for _ in 0..MEMORY_WINDOW {
+            logger.move_zord("mroik", (0, 0), (1, 1));
+        }
+        assert!(logger.sync().is_ok());
+
+        // The first event is no longer in memory but is still reachable, from both directions
+        let query = EventQuery {
+            before: Some(5),
+            ..Default::default()
+        };
+        assert_eq!(ids(&logger, &query), vec![4, 3, 2, 1, 0]);
+        let query = EventQuery {
+            kind: Some(String::from("totem_spawned")),
+            ..Default::default()
+        };
+        assert_eq!(ids(&logger, &query), vec![0]);
+        let query = EventQuery {
+            after: Some(0),
+            limit: Some(1),
+            ..Default::default()
+        };
+        assert_eq!(ids(&logger, &query), vec![1]);
+        let query = EventQuery {
+            after: Some(MEMORY_WINDOW as u64 - 2),
+            ..Default::default()
+        };
+        assert_eq!(
+            ids(&logger, &query),
+            vec![MEMORY_WINDOW as u64, MEMORY_WINDOW as u64 - 1]
+        );
+        assert_eq!(logger.oldest_first().count(), MEMORY_WINDOW + 1);
+        let from_day = logger.oldest_first_from(1).map(|event| event.id());
+        assert!(from_day.eq(1..=MEMORY_WINDOW as u64));
+
+        // Reattaching picks up where the journal left off
+        let mut restored = Logger::new();
+        restored.attach_journal(&dir, FsyncPolicy::Never).unwrap();
+        restored.respawn("fin", (2, 2));
+        fs::remove_dir_all(&dir).unwrap();
+        let query = EventQuery {
+            limit: Some(1),
+            ..Default::default()
+        };
+        assert_eq!(ids(&restored, &query), vec![MEMORY_WINDOW as u64 + 1]);
+        assert_eq!(restored.counts().get("move"), Some(&(MEMORY_WINDOW as u64)));
+    }
+
+    #[test]
+    fn cursor_older_than_memory() {
+        let dir = env::temp_dir().join(format!("woop-cursor-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+
+        let mut logger = Logger::new();
+        logger.attach_journal(&dir, FsyncPolicy::Never).unwrap();
+        logger.totem_spawned((0, 0));
+        for day in 1..=2 {
+            logger.new_day(day);
+            for _ in 0..MEMORY_WINDOW {
+                logger.move_zord("mroik", (0, 0), (1, 1));
+            }
+        }
+        assert!(logger.sync().is_ok());
+
+        // Cursors in the journal of either day and in memory
+        let page = |after: usize| {
+            let query = EventQuery {
+                after: Some(after as u64),
+                limit: Some(2),
+                ..Default::default()
+            };
+            ids(&logger, &query)
+        };
+        assert_eq!(page(5), vec![7, 6]);
+        assert_eq!(
+            page(MEMORY_WINDOW),
+            vec![MEMORY_WINDOW as u64 + 2, MEMORY_WINDOW as u64 + 1]
+        );
+        assert_eq!(page(MEMORY_WINDOW * 2 - 1), vec![MEMORY_WINDOW as u64 * 2]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
 }
@@ -0,0 +1,363 @@
+use super::error::WoopError;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use utoipa::ToSchema;
+
+/// Longest message that can be sent, in characters
+pub const MAX_MESSAGE_LENGTH: usize = 2000;
+/// Messages a player can send in a day
+pub const MAX_MESSAGES_PER_DAY: usize = 200;
+/// Groups a player can be a member of
+pub const MAX_GROUPS: usize = 10;
+
+/// Where a message is sent, from the point of view of the player looking at it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Channel {
+    /// Conversation with another player
+    Direct(String),
+    /// Conversation between the members of a group
+    Group(String),
+}
+
+impl Channel {
+    fn key(&self) -> String {
+        match self {
+            Self::Direct(player) => format!("direct:{}", player),
+            Self::Group(group) => format!("group:{}", group),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Message {
+    pub id: u64,
+    pub day: u8,
+    pub from: String,
+    /// The recipient of a direct message or the group it was sent to
+    pub channel: Channel,
+    pub body: String,
+    /// Unix timestamp
+    pub sent_at: u64,
+}
+
+/// A conversation as seen in the inbox of a player
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Conversation<'a> {
+    pub channel: Channel,
+    /// Messages of the others not read yet
+    pub unread: usize,
+    pub last: &'a Message,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Inbox<'a> {
+    /// Unread messages across every conversation
+    pub unread: usize,
+    /// Most recent first
+    pub conversations: Vec<Conversation<'a>>,
+    /// Groups the player is a member of
+    pub groups: Vec<&'a str>,
+    /// Groups the player was invited to and hasn't joined
+    pub invites: Vec<&'a str>,
+}
+
+/// Direct messages and group channels of a game. Messages are private to the players in the
+/// conversation, and to everyone once the game ended when the config reveals them.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Mailbox {
+    next_id: u64,
+    messages: Vec<Message>,
+    groups: BTreeMap<String, BTreeSet<String>>,
+    /// Players invited to each group that haven't joined it yet
+    #[serde(default)]
+    invites: BTreeMap<String, BTreeSet<String>>,
+    /// Id of the last message read by each player in each of their conversations
+    read: BTreeMap<String, BTreeMap<String, u64>>,
+}
+
+impl Mailbox {
+    fn check_group_limit(&self, player: &str) -> Result<(), WoopError> {
+        let groups = self
+            .groups
+            .values()
+            .filter(|members| members.contains(player))
+            .count();
+        match groups < MAX_GROUPS {
+            true => Ok(()),
+            false => Err(WoopError::TooManyGroups),
+        }
+    }
+
+    /// Creates a group of `creator` and invites `members` to it, nobody else can join afterwards
+    pub fn create_group(
+        &mut self,
+        name: &str,
+        creator: &str,
+        members: &[String],
+    ) -> Result<(), WoopError> {
+        if self.groups.contains_key(name) {
+            return Err(WoopError::GroupNameTaken(name.to_string()));
+        }
+        self.check_group_limit(creator)?;
+        let invited: BTreeSet<String> = members
+            .iter()
+            .filter(|member| *member != creator)
+            .cloned()
+            .collect();
+        self.groups
+            .insert(name.to_string(), BTreeSet::from([creator.to_string()]));
+        self.invites.insert(name.to_string(), invited);
+        Ok(())
+    }
+
+    /// Makes `player` a member of a group they were invited to
+    pub fn join_group(&mut self, name: &str, player: &str) -> Result<(), WoopError> {
+        if !self
+            .invites
+            .get(name)
+            .is_some_and(|invited| invited.contains(player))
+        {
+            return Err(WoopError::NotInvitedToGroup(name.to_string()));
+        }
+        self.check_group_limit(player)?;
+        self.invites.get_mut(name).unwrap().remove(player);
+        self.groups
+            .get_mut(name)
+            .unwrap()
+            .insert(player.to_string());
+        Ok(())
+    }
+
+    pub fn send(
+        &mut self,
+        from: &str,
+        channel: Channel,
+        body: String,
+        day: u8,
+        sent_at: u64,
+    ) -> Result<&Message, WoopError> {
+        if body.chars().count() > MAX_MESSAGE_LENGTH {
+            return Err(WoopError::MessageTooLong);
+        }
+        let sent_today = self
+            .messages
+            .iter()
+            .rev()
+            .take_while(|message| message.day == day)
+            .filter(|message| message.from == from)
+            .count();
+        if sent_today >= MAX_MESSAGES_PER_DAY {
+            return Err(WoopError::TooManyMessages);
+        }
+        if let Channel::Group(group) = &channel {
+            self.check_member(group, from)?;
+        }
+        self.messages.push(Message {
+            id: self.next_id,
+            day,
+            from: from.to_string(),
+            channel,
+            body,
+            sent_at,
+        });
+        self.next_id += 1;
+        Ok(self.messages.last().unwrap())
+    }
+
+    fn check_member(&self, group: &str, player: &str) -> Result<(), WoopError> {
+        match self.groups.get(group) {
+            Some(members) if members.contains(player) => Ok(()),
+            _ => Err(WoopError::GroupNotFound(group.to_string())),
+        }
+    }
+
+    /// The conversation `message` belongs to for `player`, `None` when they can't read it
+    pub fn conversation(&self, message: &Message, player: &str) -> Option<Channel> {
+        match &message.channel {
+            Channel::Direct(to) if message.from == player => Some(Channel::Direct(to.clone())),
+            Channel::Direct(to) if to == player => Some(Channel::Direct(message.from.clone())),
+            Channel::Direct(_) => None,
+            Channel::Group(group) => self
+                .check_member(group, player)
+                .ok()
+                .map(|_| Channel::Group(group.clone())),
+        }
+    }
+
+    pub fn inbox(&self, player: &str) -> Inbox<'_> {
+        let read = self.read.get(player);
+        let mut conversations: BTreeMap<String, Conversation> = BTreeMap::new();
+        for message in self.messages.iter() {
+            let Some(channel) = self.conversation(message, player) else {
+                continue;
+            };
+            let key = channel.key();
+            let unread = message.from != player
+                && read
+                    .and_then(|read| read.get(&key))
+                    .is_none_or(|last_read| message.id > *last_read);
+            let conversation = conversations.entry(key).or_insert(Conversation {
+                channel,
+                unread: 0,
+                last: message,
+            });
+            conversation.last = message;
+            conversation.unread += unread as usize;
+        }
+        let mut conversations: Vec<Conversation> = conversations.into_values().collect();
+        conversations.sort_by_key(|conversation| std::cmp::Reverse(conversation.last.id));
+        Inbox {
+            unread: conversations.iter().map(|c| c.unread).sum(),
+            conversations,
+            groups: self
+                .groups
+                .iter()
+                .filter(|(_, members)| members.contains(player))
+                .map(|(name, _)| name.as_str())
+                .collect(),
+            invites: self
+                .invites
+                .iter()
+                .filter(|(_, invited)| invited.contains(player))
+                .map(|(name, _)| name.as_str())
+                .collect(),
+        }
+    }
+
+    /// Messages of a conversation of `player`, oldest first, which are marked as read
+    pub fn read(&mut self, player: &str, channel: &Channel) -> Result<Vec<Message>, WoopError> {
+        if let Channel::Group(group) = channel {
+            self.check_member(group, player)?;
+        }
+        let messages: Vec<Message> = self
+            .messages
+            .iter()
+            .filter(|message| self.conversation(message, player).as_ref() == Some(channel))
+            .cloned()
+            .collect();
+        if let Some(last) = messages.last() {
+            self.read
+                .entry(player.to_string())
+                .or_default()
+                .insert(channel.key(), last.id);
+        }
+        Ok(messages)
+    }
+
+    pub fn all(&self) -> &[Message] {
+        &self.messages
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Channel, Mailbox, MAX_GROUPS, MAX_MESSAGES_PER_DAY};
+    use crate::game::error::WoopError;
+
+    #[test]
+    fn unread_messages() {
+        let mut mailbox = Mailbox::default();
+        let direct = |to: &str| Channel::Direct(to.to_string());
+        mailbox
+            .send("mroik", direct("fin"), String::from("truce?"), 1, 10)
+            .unwrap();
+        mailbox
+            .send("fin", direct("mroik"), String::from("sure"), 1, 11)
+            .unwrap();
+        mailbox
+            .create_group("council", "fin", &[String::from("carl")])
+            .unwrap();
+        assert_eq!(mailbox.inbox("carl").invites, vec!["council"]);
+        assert!(matches!(
+            mailbox.join_group("council", "mroik"),
+            Err(WoopError::NotInvitedToGroup(_))
+        ));
+        mailbox.join_group("council", "carl").unwrap();
+        assert!(mailbox.inbox("carl").invites.is_empty());
+        assert!(matches!(
+            mailbox.send(
+                "mroik",
+                Channel::Group(String::from("council")),
+                String::new(),
+                1,
+                12
+            ),
+            Err(WoopError::GroupNotFound(_))
+        ));
+        mailbox
+            .send(
+                "carl",
+                Channel::Group(String::from("council")),
+                String::from("hi"),
+                1,
+                13,
+            )
+            .unwrap();
+
+        let inbox = mailbox.inbox("fin");
+        assert_eq!(inbox.unread, 2);
+        assert_eq!(inbox.conversations.len(), 2);
+        assert_eq!(
+            inbox.conversations[0].channel,
+            Channel::Group(String::from("council"))
+        );
+        assert_eq!(mailbox.inbox("mroik").unread, 1);
+        assert_eq!(mailbox.inbox("carl").conversations.len(), 1);
+
+        let read = mailbox.read("fin", &direct("mroik")).unwrap();
+        assert_eq!(read.len(), 2);
+        assert_eq!(mailbox.inbox("fin").unread, 1);
+        assert!(mailbox
+            .read("mroik", &Channel::Group(String::from("council")))
+            .is_err());
+    }
+
+    #[test]
+    fn messages_and_groups_are_capped() {
+        let mut mailbox = Mailbox::default();
+        let direct = Channel::Direct(String::from("fin"));
+        for _ in 0..MAX_MESSAGES_PER_DAY {
+            mailbox
+                .send("mroik", direct.clone(), String::from("hi"), 1, 10)
+                .unwrap();
+        }
+        assert!(matches!(
+            mailbox.send("mroik", direct.clone(), String::from("hi"), 1, 10),
+            Err(WoopError::TooManyMessages)
+        ));
+        mailbox
+            .send(
+                "fin",
+                Channel::Direct(String::from("mroik")),
+                String::new(),
+                1,
+                10,
+            )
+            .unwrap();
+        mailbox
+            .send("mroik", direct, String::from("hi"), 2, 20)
+            .unwrap();
+
+        let invited = [String::from("fin")];
+        for group in 0..MAX_GROUPS {
+            mailbox
+                .create_group(group.to_string().as_str(), "mroik", &invited)
+                .unwrap();
+        }
+        assert!(matches!(
+            mailbox.create_group("more", "mroik", &[]),
+            Err(WoopError::TooManyGroups)
+        ));
+        mailbox.create_group("more", "carl", &invited).unwrap();
+        for group in 0..MAX_GROUPS {
+            mailbox
+                .join_group(group.to_string().as_str(), "fin")
+                .unwrap();
+        }
+        assert!(matches!(
+            mailbox.join_group("more", "fin"),
+            Err(WoopError::TooManyGroups)
+        ));
+    }
+}
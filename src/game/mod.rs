@@ -1,6 +1,24 @@
+pub mod action;
+pub mod bot;
+pub mod clock;
 pub mod error;
+pub mod feed;
+#[cfg(test)]
+pub mod fixtures;
+#[allow(clippy::module_inception)]
 pub mod game;
+pub mod graph;
+pub mod journal;
 pub mod log;
+pub mod messages;
+pub mod orders;
 pub mod player;
+pub mod replay;
+pub mod replies;
+pub mod stats;
+pub mod tactics;
+pub mod team;
 pub mod totem;
+pub mod treaty;
+pub mod vision;
 pub mod zord;
@@ -0,0 +1,271 @@
+use super::{
+    action::Action,
+    error::WoopError,
+    team::Teams,
+    zord::{distance, Zord},
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Orders a player can have waiting at the same time
+pub const MAX_ORDERS: usize = 20;
+
+/// An action left for the server to play on behalf of a player
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case", tag = "order")]
+pub enum Order {
+    /// Plays `action` once `time` has come
+    At {
+        /// Unix timestamp
+        time: u64,
+        action: Action,
+    },
+    /// Shoots the first enemy zord that comes in range of the own zord in `coord`
+    ShootInRange { coord: (i16, i16) },
+    /// Plays `action` once an enemy zord comes within `distance` of the own zord in `coord`
+    EnemyWithin {
+        coord: (i16, i16),
+        distance: u16,
+        action: Action,
+    },
+}
+
+impl Order {
+    /// Action to play now, `None` while the order has to wait. Conditions are only checked after
+    /// the grace period, as there would be nothing to shoot at before.
+    pub fn due(
+        &self,
+        player: &str,
+        zords: &[Zord],
+        teams: &Teams,
+        now: u64,
+        grace_period: bool,
+    ) -> Option<Result<Action, WoopError>> {
+        let (coord, reach) = match self {
+            Self::At { time, action } => return (now >= *time).then(|| Ok(action.clone())),
+            _ if grace_period => return None,
+            Self::ShootInRange { coord } => (*coord, None),
+            Self::EnemyWithin {
+                coord, distance, ..
+            } => (*coord, Some(*distance)),
+        };
+        let Some(own) = zords
+            .iter()
+            .find(|zord| (zord.x, zord.y) == coord && zord.owner == player)
+        else {
+            return Some(Err(WoopError::ZordNotFound(coord.0, coord.1)));
+        };
+        let reach = reach.unwrap_or(own.range as u16);
+        let enemy = zords
+            .iter()
+            .filter(|zord| {
+                zord.owner != player
+                    && !teams.allies(player, zord.owner.as_str())
+                    && distance((own.x, own.y), (zord.x, zord.y)) as u16 <= reach
+            })
+            .min_by_key(|zord| distance((own.x, own.y), (zord.x, zord.y)))?;
+        match self {
+            Self::EnemyWithin { action, .. } => Some(Ok(action.clone())),
+            _ => Some(Ok(Action::Shoot {
+                from: coord,
+                to: (enemy.x, enemy.y),
+            })),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct QueuedOrder {
+    pub id: u64,
+    pub player: String,
+    #[serde(flatten)]
+    pub order: Order,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct PlayedOrder {
+    #[serde(flatten)]
+    pub order: QueuedOrder,
+    /// Unix timestamp
+    pub played_at: u64,
+    /// Why the action was refused, missing when it went through
+    pub error: Option<String>,
+}
+
+/// Orders waiting to be played and the ones played since the start of the day
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Orders {
+    next_id: u64,
+    pending: Vec<QueuedOrder>,
+    played: Vec<PlayedOrder>,
+}
+
+impl Orders {
+    pub fn add(&mut self, player: &str, order: Order) -> Result<&QueuedOrder, WoopError> {
+        if self.pending(player).count() >= MAX_ORDERS {
+            return Err(WoopError::TooManyOrders);
+        }
+        self.pending.push(QueuedOrder {
+            id: self.next_id,
+            player: player.to_string(),
+            order,
+        });
+        self.next_id += 1;
+        Ok(self.pending.last().unwrap())
+    }
+
+    pub fn cancel(&mut self, player: &str, id: u64) -> Result<QueuedOrder, WoopError> {
+        match self
+            .pending
+            .iter()
+            .position(|order| order.id == id && order.player == player)
+        {
+            Some(index) => Ok(self.pending.remove(index)),
+            None => Err(WoopError::OrderNotFound(id)),
+        }
+    }
+
+    pub fn pending<'a>(&'a self, player: &'a str) -> impl Iterator<Item = &'a QueuedOrder> {
+        self.pending
+            .iter()
+            .filter(move |order| order.player == player)
+    }
+
+    pub fn played<'a>(&'a self, player: &'a str) -> impl Iterator<Item = &'a PlayedOrder> {
+        self.played
+            .iter()
+            .filter(move |played| played.order.player == player)
+    }
+
+    /// Some order would be taken by `take_due`
+    pub fn any_due(&self, zords: &[Zord], teams: &Teams, now: u64, grace_period: bool) -> bool {
+        self.pending.iter().any(|queued| {
+            queued
+                .order
+                .due(queued.player.as_str(), zords, teams, now, grace_period)
+                .is_some()
+        })
+    }
+
+    /// Takes the orders that are due out of the queue, oldest first
+    pub fn take_due(
+        &mut self,
+        zords: &[Zord],
+        teams: &Teams,
+        now: u64,
+        grace_period: bool,
+    ) -> Vec<(QueuedOrder, Result<Action, WoopError>)> {
+        let mut due = Vec::new();
+        self.pending.retain(|queued| {
+            match queued
+                .order
+                .due(queued.player.as_str(), zords, teams, now, grace_period)
+            {
+                Some(action) => {
+                    due.push((queued.clone(), action));
+                    false
+                }
+                None => true,
+            }
+        });
+        due
+    }
+
+    pub fn played_order(&mut self, order: QueuedOrder, played_at: u64, error: Option<String>) {
+        self.played.push(PlayedOrder {
+            order,
+            played_at,
+            error,
+        });
+    }
+
+    /// Forgets the orders played on the day that ended
+    pub fn new_day(&mut self) {
+        self.played.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Order, Orders, MAX_ORDERS};
+    use crate::game::{action::Action, error::WoopError, team::Teams, zord::Zord};
+
+    #[test]
+    fn trigger_orders() {
+        // Allies are never taken for enemies
+        let mut zords = vec![
+            Zord::new("mroik", 0, 0),
+            Zord::new("fin", 9, 9),
+            Zord::new("carl", 1, 1),
+        ];
+        let mut teams = Teams::default();
+        teams.create("red", "mroik").unwrap();
+        teams.invite("mroik", "carl").unwrap();
+        teams.accept("carl", "red").unwrap();
+        let mut orders = Orders::default();
+        let shield = Action::Shield { coord: (0, 0) };
+        orders
+            .add(
+                "mroik",
+                Order::At {
+                    time: 100,
+                    action: shield.clone(),
+                },
+            )
+            .unwrap();
+        orders
+            .add("mroik", Order::ShootInRange { coord: (0, 0) })
+            .unwrap();
+        orders
+            .add(
+                "mroik",
+                Order::EnemyWithin {
+                    coord: (0, 0),
+                    distance: 8,
+                    action: shield.clone(),
+                },
+            )
+            .unwrap();
+
+        assert!(orders.take_due(&zords, &teams, 99, false).is_empty());
+        let due = orders.take_due(&zords, &teams, 100, false);
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].1.as_ref().unwrap(), &shield);
+
+        (zords[1].x, zords[1].y) = (4, 5);
+        assert!(orders.take_due(&zords, &teams, 100, true).is_empty());
+        let due = orders.take_due(&zords, &teams, 100, false);
+        let actions: Vec<&Action> = due.iter().map(|(_, a)| a.as_ref().unwrap()).collect();
+        assert_eq!(
+            actions,
+            vec![
+                &Action::Shoot {
+                    from: (0, 0),
+                    to: (4, 5)
+                },
+                &shield
+            ]
+        );
+        assert_eq!(orders.pending("mroik").count(), 0);
+    }
+
+    #[test]
+    fn queue_limits() {
+        let mut orders = Orders::default();
+        for _ in 0..MAX_ORDERS {
+            orders
+                .add("mroik", Order::ShootInRange { coord: (0, 0) })
+                .unwrap();
+        }
+        assert!(matches!(
+            orders.add("mroik", Order::ShootInRange { coord: (0, 0) }),
+            Err(WoopError::TooManyOrders)
+        ));
+        assert!(matches!(
+            orders.cancel("fin", 3),
+            Err(WoopError::OrderNotFound(3))
+        ));
+        assert_eq!(orders.cancel("mroik", 3).unwrap().id, 3);
+        assert_eq!(orders.pending("mroik").count(), MAX_ORDERS - 1);
+    }
+}
@@ -1,14 +1,17 @@
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
 pub const BASE_ACTIONS: u8 = 20;
 
-#[derive(PartialEq, Clone, Debug, Serialize, ToSchema)]
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "snake_case")]
 pub struct Player {
     pub name: String,
     pub actions: u8,
     pub points: u16,
+    /// Played by the server, see the bots of the config
+    #[serde(default)]
+    pub bot: bool,
 }
 
 impl Player {
@@ -17,6 +20,14 @@ impl Player {
             name: name.to_string(),
             actions: BASE_ACTIONS,
             points: 0,
+            bot: false,
+        }
+    }
+
+    pub fn bot(name: &str) -> Self {
+        Player {
+            bot: true,
+            ..Player::new(name)
         }
     }
 
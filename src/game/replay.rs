@@ -0,0 +1,331 @@
+use super::{
+    game::{Game, ACTION_COST},
+    log::PlayerEvent,
+    player::{Player, BASE_ACTIONS},
+    totem::Totem,
+    zord::{distance, Zord, BASE_RANGE},
+};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use utoipa::ToSchema;
+
+/// The board as it was at some point in the past
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PastBoard {
+    pub day: u8,
+    /// Unix timestamp the board is shown at
+    pub time: u64,
+    /// Sorted by points
+    pub players: Vec<Player>,
+    pub zords: Vec<Zord>,
+    pub totems: (Totem, Totem),
+}
+
+fn spend(players: &mut BTreeMap<String, Player>, player: &str, actions: u8, points: u16) {
+    if let Some(player) = players.get_mut(player) {
+        player.actions = player.actions.saturating_sub(actions);
+        player.points = player.points.saturating_sub(points);
+    }
+}
+
+fn zord_at(zords: &[Zord], (x, y): (i16, i16)) -> Option<usize> {
+    zords.iter().position(|zord| (zord.x, zord.y) == (x, y))
+}
+
+// Mirrors what `Game` does for each event
+fn replay(
+    event: &PlayerEvent,
+    players: &mut BTreeMap<String, Player>,
+    zords: &mut Vec<Zord>,
+    spawned: &mut Vec<Totem>,
+) {
+    match event {
+        PlayerEvent::Shoot {
+            shooter,
+            to,
+            shielded,
+            hp_left,
+            destroyed,
+            reward,
+            ..
+        } => {
+            spend(players, shooter, ACTION_COST, 0);
+            if let Some(index) = zord_at(zords, *to) {
+                match (destroyed, shielded) {
+                    (true, _) => drop(zords.remove(index)),
+                    (false, true) => zords[index].shields = zords[index].shields.saturating_sub(1),
+                    (false, false) => zords[index].hp = *hp_left,
+                }
+            }
+            if let Some(shooter) = players.get_mut(shooter) {
+                shooter.points += reward;
+            }
+        }
+        PlayerEvent::Move {
+            player, from, to, ..
+        } => {
+            spend(players, player, distance(*from, *to) as u8, 0);
+            if let Some(index) = zord_at(zords, *from) {
+                zords[index].set_coord(to.0, to.1);
+            }
+        }
+        PlayerEvent::GenerateShield {
+            player, zord_coord, ..
+        } => {
+            spend(players, player, ACTION_COST, 0);
+            if let Some(index) = zord_at(zords, *zord_coord) {
+                zords[index].generate_shield();
+            }
+        }
+        PlayerEvent::IncreaseRange {
+            player, zord_coord, ..
+        } => {
+            spend(players, player, ACTION_COST / 2, 0);
+            if let Some(index) = zord_at(zords, *zord_coord) {
+                zords[index].increase_range();
+            }
+        }
+        PlayerEvent::DonatePoints {
+            from, to, amount, ..
+        } => {
+            spend(players, from, ACTION_COST, *amount);
+            if let Some(to) = players.get_mut(to) {
+                to.points += amount;
+            }
+        }
+        PlayerEvent::BuildZord {
+            player,
+            zord_coord,
+            cost,
+            ..
+        } => {
+            spend(players, player, ACTION_COST, *cost);
+            let mut zord = Zord::new(player, zord_coord.0, zord_coord.1);
+            zord.hit();
+            zords.push(zord);
+        }
+        PlayerEvent::TotemPoints { player, points, .. } => {
+            if let Some(player) = players.get_mut(player) {
+                player.points += points;
+            }
+        }
+        PlayerEvent::Respawn { player, coord, .. } => {
+            zords.push(Zord::new(player, coord.0, coord.1));
+        }
+        PlayerEvent::TotemSpawned { coord, .. } => spawned.push(Totem::new(coord.0, coord.1)),
+        PlayerEvent::ShieldExpired { zord_coord, .. } => {
+            if let Some(index) = zord_at(zords, *zord_coord) {
+                zords[index].shields = 0;
+            }
+        }
+        PlayerEvent::RangeReset { zord_coord, .. } => {
+            if let Some(index) = zord_at(zords, *zord_coord) {
+                zords[index].range = BASE_RANGE;
+            }
+        }
+        PlayerEvent::PointPenalty {
+            player,
+            points_left,
+            ..
+        } => {
+            if let Some(player) = players.get_mut(player) {
+                player.points = *points_left;
+            }
+        }
+        PlayerEvent::TreatyPoints {
+            from, to, amount, ..
+        } => {
+            if let Some(from) = from {
+                spend(players, from, 0, *amount);
+            }
+            if let Some(to) = to.as_ref().and_then(|to| players.get_mut(to)) {
+                to.points += amount;
+            }
+        }
+        PlayerEvent::TeamCreated { .. }
+        | PlayerEvent::TeamInvite { .. }
+        | PlayerEvent::TeamJoined { .. }
+        | PlayerEvent::TeamLeft { .. }
+        | PlayerEvent::TreatyUpdate { .. } => (),
+    }
+}
+
+/// Rebuilds the board as it was at `time`, starting from the state at the end of the day before
+/// and replaying the events logged up to then
+pub fn board_at(game: &Game, time: u64) -> PastBoard {
+    let day = game
+        .history
+        .iter()
+        .find(|state| state.ended_at > time)
+        .map_or(game.day, |state| state.day);
+    let before = game
+        .history
+        .iter()
+        .find(|state| day > 1 && state.day == day - 1);
+
+    let mut players: BTreeMap<String, Player> = match before {
+        Some(state) => state
+            .players
+            .iter()
+            .map(|player| (player.name.clone(), player.clone()))
+            .collect(),
+        None => game
+            .players
+            .values()
+            .map(|player| {
+                let fresh = Player {
+                    bot: player.bot,
+                    ..Player::new(player.name.as_str())
+                };
+                (player.name.clone(), fresh)
+            })
+            .collect(),
+    };
+    players
+        .values_mut()
+        .for_each(|player| player.actions = BASE_ACTIONS);
+    let mut zords = before.map_or_else(Vec::new, |state| state.zords.clone());
+    let mut totems = before
+        .and_then(|state| state.totems.clone())
+        .unwrap_or_else(|| game.totems.clone());
+
+    let mut spawned = Vec::new();
+    game.logged_actions
+        .oldest_first_from(day)
+        .take_while(|event| event.day() == day && event.timestamp() <= time)
+        .for_each(|event| replay(&event, &mut players, &mut zords, &mut spawned));
+    if let [.., first, second] = spawned.as_slice() {
+        totems = (first.clone(), second.clone());
+    }
+
+    let mut players: Vec<Player> = players.into_values().collect();
+    players.sort_by_key(|player| std::cmp::Reverse(player.points));
+    PastBoard {
+        day,
+        time,
+        players,
+        zords,
+        totems,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::board_at;
+    use crate::{
+        config::Config,
+        game::{
+            clock::Clock,
+            game::{Game, GRACE_PERIOD},
+            player::{Player, BASE_ACTIONS},
+            zord::Zord,
+        },
+    };
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn rebuild_past_boards() {
+        let config = Config {
+            start_of_game: UNIX_EPOCH,
+            seed: Some(7),
+            ..Config::with_players("test", &["mroik", "fin"])
+        };
+        let clock = Clock::manual(UNIX_EPOCH);
+        let mut game = Game::new(&config);
+        game.set_clock(clock.clone());
+        game.new_day();
+        clock.advance(Duration::from_secs(GRACE_PERIOD + 1));
+
+        let mroik = game.zords.iter().find(|z| z.owner == "mroik").unwrap();
+        let (x, y) = (mroik.x, mroik.y);
+        game.generate_shield("mroik", x, y).unwrap();
+        let shielded = clock.unix_timestamp();
+        clock.advance(Duration::from_secs(60));
+        let to = if x > 0 { (x - 1, y) } else { (x + 1, y) };
+        game.move_zord("mroik", x, y, to.0, to.1).unwrap();
+
+        let board = board_at(&game, shielded);
+        let zord = board.zords.iter().find(|z| z.owner == "mroik").unwrap();
+        assert_eq!((zord.x, zord.y, zord.shields), (x, y, 1));
+        let player = board.players.iter().find(|p| p.name == "mroik").unwrap();
+        assert_eq!(player.actions, BASE_ACTIONS - 4);
+
+        let yesterday = clock.unix_timestamp();
+        clock.advance(Duration::from_secs(60 * 60 * 24));
+        game.new_day();
+        clock.advance(Duration::from_secs(60));
+
+        let board = board_at(&game, yesterday);
+        assert_eq!(board.day, 1);
+        let zord = board.zords.iter().find(|z| z.owner == "mroik").unwrap();
+        assert_eq!((zord.x, zord.y), to);
+
+        let now = board_at(&game, clock.unix_timestamp());
+        assert_eq!(now.day, 2);
+        assert_eq!(now.zords.len(), game.zords.len());
+        assert!(now.zords.iter().all(|zord| zord.shields == 0));
+        assert_eq!(
+            (now.totems.0.x, now.totems.0.y),
+            (game.totems.0.x, game.totems.0.y)
+        );
+        assert!(now.players.iter().all(|p| p.actions == BASE_ACTIONS));
+    }
+
+    #[test]
+    fn replay_matches_the_game() {
+        let config = Config {
+            start_of_game: UNIX_EPOCH,
+            seed: Some(7),
+            ..Config::with_players("test", &["mroik", "fin"])
+        };
+        let clock = Clock::manual(UNIX_EPOCH);
+        let mut game = Game::new(&config);
+        game.set_clock(clock.clone());
+        game.new_day();
+        game.zords = vec![
+            Zord::new("mroik", 70, 70),
+            Zord::new("mroik", 71, 70),
+            Zord::new("fin", 73, 70),
+            Zord::new("fin", 74, 70),
+        ];
+        game.players
+            .values_mut()
+            .for_each(|player| player.points = 30);
+        clock.advance(Duration::from_secs(60 * 60 * 24));
+        game.new_day();
+        clock.advance(Duration::from_secs(GRACE_PERIOD + 1));
+
+        game.generate_shield("mroik", 70, 70).unwrap();
+        game.increase_range("mroik", 71, 70).unwrap();
+        game.move_zord("mroik", 71, 70, 72, 71).unwrap();
+        game.player_shoot("mroik", 70, 70, 73, 70).unwrap();
+        game.player_shoot("mroik", 72, 71, 73, 70).unwrap();
+        game.donate_points("fin", "mroik", 5).unwrap();
+        game.build_zord("fin", 75, 70).unwrap();
+        game.player_shoot("fin", 74, 70, 70, 70).unwrap();
+        game.player_shoot("fin", 74, 70, 70, 70).unwrap();
+
+        let board = board_at(&game, clock.unix_timestamp());
+        let players = |players: Vec<&Player>| -> Vec<(String, u16, u8)> {
+            let mut players: Vec<_> = players
+                .into_iter()
+                .map(|player| (player.name.clone(), player.points, player.actions))
+                .collect();
+            players.sort();
+            players
+        };
+        assert_eq!(
+            players(board.players.iter().collect()),
+            players(game.players.values().collect())
+        );
+        let zords = |zords: &[Zord]| -> Vec<(String, i16, i16, u8, u8, u8)> {
+            let mut zords: Vec<_> = zords
+                .iter()
+                .map(|z| (z.owner.clone(), z.x, z.y, z.hp, z.shields, z.range))
+                .collect();
+            zords.sort();
+            zords
+        };
+        assert_eq!(zords(&board.zords), zords(&game.zords));
+    }
+}
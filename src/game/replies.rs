@@ -0,0 +1,68 @@
+use super::error::WoopError;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+/// Idempotency keys a player can use in a day
+pub const MAX_KEYS: usize = 100;
+
+/// Identifies a request by its route and body
+pub fn fingerprint(route: &str, body: &impl Serialize) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(route);
+    hasher.update(serde_json::to_vec(body).unwrap());
+    URL_SAFE_NO_PAD.encode(hasher.finalize())
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Reply {
+    /// Fingerprint of the request the key was first used with
+    request: String,
+    reply: Value,
+}
+
+/// Replies to the actions sent with an idempotency key, so that a request retried with the same
+/// key gets the original reply instead of being played again. A key can't be reused for a
+/// different request. Keys expire at rollover.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Replies {
+    // Keyed by player and then by idempotency key
+    replies: HashMap<String, HashMap<String, Reply>>,
+}
+
+impl Replies {
+    /// The reply to send again, `None` when the request has to be played. Refuses a key that was
+    /// used for another request and new keys once the player used `MAX_KEYS` of them.
+    pub fn get(
+        &self,
+        player: &str,
+        key: Option<&str>,
+        request: &str,
+    ) -> Result<Option<&Value>, WoopError> {
+        let Some(key) = key else {
+            return Ok(None);
+        };
+        let replies = self.replies.get(player);
+        match replies.and_then(|replies| replies.get(key)) {
+            Some(reply) if reply.request == request => Ok(Some(&reply.reply)),
+            Some(_) => Err(WoopError::KeyReused(key.to_string())),
+            None if replies.is_some_and(|replies| replies.len() >= MAX_KEYS) => {
+                Err(WoopError::TooManyKeys)
+            }
+            None => Ok(None),
+        }
+    }
+
+    pub fn insert(&mut self, player: &str, key: String, request: String, reply: Value) {
+        self.replies
+            .entry(player.to_string())
+            .or_default()
+            .insert(key, Reply { request, reply });
+    }
+
+    pub fn clear(&mut self) {
+        self.replies.clear();
+    }
+}
@@ -0,0 +1,215 @@
+use super::{game::ACTION_COST, log::PlayerEvent, zord};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use utoipa::ToSchema;
+
+/// Counters of a player, either over the whole game or over a single day
+#[derive(Debug, Default, Clone, PartialEq, Serialize, ToSchema)]
+pub struct PlayerStats {
+    /// Enemy zords destroyed
+    pub kills: u64,
+    /// Own zords destroyed by someone else
+    pub deaths: u64,
+    /// Hits that took hp off an enemy zord
+    pub damage_dealt: u64,
+    /// Hits that took hp off an own zord
+    pub damage_taken: u64,
+    /// Hits on an own zord absorbed by its shields
+    pub damage_absorbed: u64,
+    /// Hits on an enemy zord absorbed by its shields
+    pub damage_blocked: u64,
+    pub shields_generated: u64,
+    /// Shields removed at rollover without having absorbed a hit
+    pub shields_expired: u64,
+    /// Action points spent for each kind of action
+    #[schema(value_type = BTreeMap<String, u64>)]
+    pub actions_spent: BTreeMap<&'static str, u64>,
+    pub totem_income: u64,
+    pub kill_income: u64,
+    pub donations_in: u64,
+    pub donations_out: u64,
+    /// Points spent building zords
+    pub build_spending: u64,
+    /// Points lost to penalties
+    pub penalties: u64,
+    /// Cells travelled by the zords of the player
+    pub distance_moved: u64,
+    /// Enemy zords destroyed, by owner
+    pub kills_by_target: BTreeMap<String, u64>,
+    /// Own zords lost, by shooter
+    pub deaths_by_shooter: BTreeMap<String, u64>,
+}
+
+impl PlayerStats {
+    fn spend(&mut self, action: &'static str, amount: u64) {
+        *self.actions_spent.entry(action).or_insert(0) += amount;
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize, ToSchema)]
+pub struct PlayerRecord {
+    pub total: PlayerStats,
+    /// Counters of each game day
+    pub days: BTreeMap<u8, PlayerStats>,
+}
+
+/// Per player counters, updated one event at a time as they are logged
+#[derive(Debug, Default)]
+pub struct Stats {
+    players: BTreeMap<String, PlayerRecord>,
+}
+
+impl Stats {
+    pub fn record(&mut self, event: &PlayerEvent) {
+        let day = event.day();
+        match event {
+            PlayerEvent::Shoot {
+                shooter,
+                target,
+                shielded,
+                destroyed,
+                reward,
+                ..
+            } => {
+                self.update(shooter, day, |stats| {
+                    stats.spend(event.kind(), ACTION_COST as u64);
+                    stats.kill_income += *reward as u64;
+                    match shielded {
+                        true => stats.damage_blocked += 1,
+                        false => stats.damage_dealt += 1,
+                    }
+                    if *destroyed {
+                        stats.kills += 1;
+                        *stats.kills_by_target.entry(target.clone()).or_insert(0) += 1;
+                    }
+                });
+                self.update(target, day, |stats| {
+                    match shielded {
+                        true => stats.damage_absorbed += 1,
+                        false => stats.damage_taken += 1,
+                    }
+                    if *destroyed {
+                        stats.deaths += 1;
+                        *stats.deaths_by_shooter.entry(shooter.clone()).or_insert(0) += 1;
+                    }
+                });
+            }
+            PlayerEvent::Move {
+                player, from, to, ..
+            } => {
+                let distance = zord::distance(*from, *to) as u64;
+                self.update(player, day, |stats| {
+                    stats.spend(event.kind(), distance);
+                    stats.distance_moved += distance;
+                });
+            }
+            PlayerEvent::GenerateShield { player, .. } => self.update(player, day, |stats| {
+                stats.spend(event.kind(), ACTION_COST as u64);
+                stats.shields_generated += 1;
+            }),
+            PlayerEvent::IncreaseRange { player, .. } => self.update(player, day, |stats| {
+                stats.spend(event.kind(), (ACTION_COST / 2) as u64);
+            }),
+            PlayerEvent::DonatePoints {
+                from, to, amount, ..
+            } => {
+                self.update(from, day, |stats| {
+                    stats.spend(event.kind(), ACTION_COST as u64);
+                    stats.donations_out += *amount as u64;
+                });
+                self.update(to, day, |stats| stats.donations_in += *amount as u64);
+            }
+            PlayerEvent::BuildZord { player, cost, .. } => self.update(player, day, |stats| {
+                stats.spend(event.kind(), ACTION_COST as u64);
+                stats.build_spending += *cost as u64;
+            }),
+            PlayerEvent::TotemPoints { player, points, .. } => {
+                self.update(player, day, |stats| stats.totem_income += *points as u64)
+            }
+            PlayerEvent::ShieldExpired {
+                player, shields, ..
+            } => self.update(player, day, |stats| {
+                stats.shields_expired += *shields as u64
+            }),
+            PlayerEvent::PointPenalty { player, points, .. } => {
+                self.update(player, day, |stats| stats.penalties += *points as u64)
+            }
+            PlayerEvent::Respawn { .. }
+            | PlayerEvent::RangeReset { .. }
+            | PlayerEvent::TotemSpawned { .. }
+            | PlayerEvent::TeamCreated { .. }
+            | PlayerEvent::TeamInvite { .. }
+            | PlayerEvent::TeamJoined { .. }
+            | PlayerEvent::TeamLeft { .. }
+            | PlayerEvent::TreatyUpdate { .. }
+            | PlayerEvent::TreatyPoints { .. } => (),
+        }
+    }
+
+    fn update(&mut self, player: &str, day: u8, change: impl Fn(&mut PlayerStats)) {
+        let record = self.players.entry(player.to_string()).or_default();
+        tally(&mut record.total, &mut record.days, day, change);
+    }
+
+    /// Counters of `player`, `None` if nothing was logged for them yet
+    pub fn player(&self, player: &str) -> Option<&PlayerRecord> {
+        self.players.get(player)
+    }
+
+    pub fn clear(&mut self) {
+        self.players.clear();
+    }
+}
+
+/// Applies the same change to the counters of the whole game and to the ones of `day`, for the
+/// aggregates of the activity log that are kept both ways
+pub fn tally<T: Default>(
+    total: &mut T,
+    days: &mut BTreeMap<u8, T>,
+    day: u8,
+    change: impl Fn(&mut T),
+) {
+    change(total);
+    change(days.entry(day).or_default());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Stats;
+    use crate::game::{fixtures::shoot, log::ShotOutcome};
+
+    fn outcome(shielded: bool, destroyed: bool) -> ShotOutcome {
+        ShotOutcome {
+            shielded,
+            hp_left: 0,
+            destroyed,
+            reward: if destroyed { 3 } else { 0 },
+            wiped_out: false,
+        }
+    }
+
+    #[test]
+    fn aggregate_combat() {
+        let mut stats = Stats::default();
+        stats.record(&shoot(0, 1, "mroik", "fin", outcome(true, false)));
+        stats.record(&shoot(1, 1, "mroik", "fin", outcome(false, false)));
+        stats.record(&shoot(2, 2, "mroik", "fin", outcome(false, true)));
+
+        let mroik = stats.player("mroik").unwrap();
+        assert_eq!(mroik.total.kills, 1);
+        assert_eq!(mroik.total.kill_income, 3);
+        assert_eq!(mroik.total.damage_dealt, 2);
+        assert_eq!(mroik.total.damage_blocked, 1);
+        assert_eq!(mroik.total.actions_spent.get("shoot"), Some(&12));
+        assert_eq!(mroik.total.kills_by_target.get("fin"), Some(&1));
+        assert_eq!(mroik.days.get(&1).unwrap().kills, 0);
+        assert_eq!(mroik.days.get(&2).unwrap().kills, 1);
+
+        let fin = stats.player("fin").unwrap();
+        assert_eq!(fin.total.deaths, 1);
+        assert_eq!(fin.total.damage_absorbed, 1);
+        assert_eq!(fin.total.damage_taken, 2);
+        assert_eq!(fin.total.deaths_by_shooter.get("mroik"), Some(&1));
+        assert!(fin.total.actions_spent.is_empty());
+    }
+}
@@ -0,0 +1,122 @@
+use super::{
+    game::{Game, ACTION_COST, MAX_DONATION_PER_ACTION, NEW_ZORD_COST, TOTEM_AURA},
+    vision::Sight,
+    zord::{distance, Zord},
+};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+/// One of the zords of a player and what is going on around it
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ZordTactics<'a> {
+    #[serde(flatten)]
+    pub zord: &'a Zord,
+    /// Enemy zords this zord can shoot, closest first
+    pub in_range: Vec<(i16, i16)>,
+    /// Enemy zords that can shoot this one, closest first
+    pub threatened_by: Vec<(i16, i16)>,
+    /// The zord is within the aura of a totem and earns a share of its points at rollover
+    pub in_totem_aura: bool,
+}
+
+/// What each action costs a player right now
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ActionCosts {
+    pub shoot: u8,
+    pub shield: u8,
+    pub increase_range: u8,
+    pub donate_points: u8,
+    /// Points that can be donated with a single action
+    pub max_donation: u16,
+    pub build_zord: u8,
+    /// Points spent on top of the action points
+    pub build_zord_points: u16,
+    /// Action points for every cell moved
+    pub move_per_cell: u8,
+    /// Farthest a zord can move with the actions left
+    pub max_move: u8,
+}
+
+// Visible zords of players outside the team matching `reaches`, closest first
+fn enemies(
+    game: &Game,
+    sight: &Sight,
+    zord: &Zord,
+    reaches: impl Fn(&Zord) -> bool,
+) -> Vec<(i16, i16)> {
+    let mut enemies: Vec<&Zord> = game
+        .zords
+        .iter()
+        .filter(|enemy| {
+            enemy.owner != zord.owner
+                && !game.teams.allies(zord.owner.as_str(), enemy.owner.as_str())
+                && sight.sees((enemy.x, enemy.y))
+        })
+        .filter(|enemy| reaches(enemy))
+        .collect();
+    enemies.sort_by_key(|enemy| distance((zord.x, zord.y), (enemy.x, enemy.y)));
+    enemies.iter().map(|enemy| (enemy.x, enemy.y)).collect()
+}
+
+pub fn zord_tactics<'a>(game: &'a Game, player: &str) -> Vec<ZordTactics<'a>> {
+    let totems = [&game.totems.0, &game.totems.1];
+    let sight = Sight::new(game, Some(player));
+    game.zords
+        .iter()
+        .filter(|zord| zord.owner == player)
+        .map(|zord| ZordTactics {
+            zord,
+            in_range: enemies(game, &sight, zord, |enemy| {
+                distance((zord.x, zord.y), (enemy.x, enemy.y)) <= zord.range as i16
+            }),
+            threatened_by: enemies(game, &sight, zord, |enemy| {
+                distance((zord.x, zord.y), (enemy.x, enemy.y)) <= enemy.range as i16
+            }),
+            in_totem_aura: totems
+                .iter()
+                .any(|totem| distance((totem.x, totem.y), (zord.x, zord.y)) <= TOTEM_AURA as i16),
+        })
+        .collect()
+}
+
+impl ActionCosts {
+    pub fn new(actions: u8) -> Self {
+        ActionCosts {
+            shoot: ACTION_COST,
+            shield: ACTION_COST,
+            increase_range: ACTION_COST / 2,
+            donate_points: ACTION_COST,
+            max_donation: MAX_DONATION_PER_ACTION,
+            build_zord: ACTION_COST,
+            build_zord_points: NEW_ZORD_COST,
+            move_per_cell: 1,
+            max_move: actions,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::zord_tactics;
+    use crate::{
+        config::Config,
+        game::{game::Game, totem::Totem, zord::Zord},
+    };
+
+    #[test]
+    fn ranges_and_threats() {
+        let config = Config::with_players("test", &["mroik", "fin"]);
+        let mut game = Game::new(&config);
+        game.totems = (Totem::new(10, 10), Totem::new(100, 100));
+        game.zords.push(Zord::new("mroik", 5, 5));
+        game.zords.push(Zord::new("fin", 11, 5));
+        game.zords.push(Zord::new("fin", 9, 9));
+        game.zords[1].range = 6;
+
+        let tactics = zord_tactics(&game, "mroik");
+        assert_eq!(tactics.len(), 1);
+        assert_eq!(tactics[0].in_range, vec![(9, 9)]);
+        assert_eq!(tactics[0].threatened_by, vec![(9, 9), (11, 5)]);
+        assert!(tactics[0].in_totem_aura);
+    }
+}
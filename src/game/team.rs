@@ -0,0 +1,158 @@
+use super::error::WoopError;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use utoipa::ToSchema;
+
+/// Optional rules for the players in a team, all of them are off by default
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(default)]
+pub struct TeamRules {
+    /// Allies can't shoot each other
+    pub no_friendly_fire: bool,
+    /// Range for donations between allies, instead of the range of the donor's zords
+    pub ally_donation_range: Option<u16>,
+    /// The totem points earned by the zords of a team are split equally among its members
+    pub pooled_totems: bool,
+}
+
+/// A team and the players in it
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Team<'a> {
+    pub name: &'a str,
+    pub members: &'a BTreeSet<String>,
+    /// Players invited to join
+    pub invited: Vec<&'a str>,
+}
+
+/// Teams declared in the config or formed during the game. A player is in one team at most and
+/// a team is disbanded once its last member leaves.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Teams {
+    teams: BTreeMap<String, BTreeSet<String>>,
+    /// Team and invited player
+    invites: BTreeSet<(String, String)>,
+}
+
+impl Teams {
+    pub fn team_of(&self, player: &str) -> Option<&str> {
+        self.teams
+            .iter()
+            .find(|(_, members)| members.contains(player))
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// Two different players in the same team
+    pub fn allies(&self, a: &str, b: &str) -> bool {
+        a != b
+            && self
+                .team_of(a)
+                .is_some_and(|team| self.team_of(b) == Some(team))
+    }
+
+    pub fn members(&self, team: &str) -> Option<&BTreeSet<String>> {
+        self.teams.get(team)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Team<'_>> {
+        self.teams.iter().map(|(name, members)| Team {
+            name,
+            members,
+            invited: self
+                .invites
+                .iter()
+                .filter(|(team, _)| team == name)
+                .map(|(_, player)| player.as_str())
+                .collect(),
+        })
+    }
+
+    pub fn create(&mut self, team: &str, player: &str) -> Result<(), WoopError> {
+        if let Some(current) = self.team_of(player) {
+            return Err(WoopError::AlreadyInTeam(current.to_string()));
+        }
+        if self.teams.contains_key(team) {
+            return Err(WoopError::TeamNameTaken(team.to_string()));
+        }
+        self.teams
+            .insert(team.to_string(), BTreeSet::from([player.to_string()]));
+        Ok(())
+    }
+
+    /// Invites `to` in the team of `from`, returns the name of the team
+    pub fn invite(&mut self, from: &str, to: &str) -> Result<String, WoopError> {
+        let Some(team) = self.team_of(from).map(String::from) else {
+            return Err(WoopError::NotInTeam);
+        };
+        if self.teams[&team].contains(to) {
+            return Err(WoopError::AlreadyInTeam(team));
+        }
+        self.invites.insert((team.clone(), to.to_string()));
+        Ok(team)
+    }
+
+    pub fn accept(&mut self, player: &str, team: &str) -> Result<(), WoopError> {
+        if let Some(current) = self.team_of(player) {
+            return Err(WoopError::AlreadyInTeam(current.to_string()));
+        }
+        if !self.invites.remove(&(team.to_string(), player.to_string())) {
+            return Err(WoopError::NotInvited(team.to_string()));
+        }
+        self.teams.get_mut(team).unwrap().insert(player.to_string());
+        Ok(())
+    }
+
+    /// Takes `player` out of their team, returns the name of the team
+    pub fn leave(&mut self, player: &str) -> Result<String, WoopError> {
+        let Some(team) = self.team_of(player).map(String::from) else {
+            return Err(WoopError::NotInTeam);
+        };
+        let members = self.teams.get_mut(&team).unwrap();
+        members.remove(player);
+        if members.is_empty() {
+            self.teams.remove(&team);
+            self.invites.retain(|(invited_to, _)| invited_to != &team);
+        }
+        Ok(team)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Teams;
+    use crate::game::error::WoopError;
+
+    #[test]
+    fn invite_and_leave() {
+        let mut teams = Teams::default();
+        teams.create("red", "mroik").unwrap();
+        assert!(matches!(
+            teams.create("red", "fin"),
+            Err(WoopError::TeamNameTaken(_))
+        ));
+        assert!(matches!(
+            teams.accept("fin", "red"),
+            Err(WoopError::NotInvited(_))
+        ));
+        assert!(matches!(
+            teams.invite("fin", "mroik"),
+            Err(WoopError::NotInTeam)
+        ));
+
+        assert_eq!(teams.invite("mroik", "fin").unwrap(), "red");
+        teams.accept("fin", "red").unwrap();
+        assert!(teams.allies("mroik", "fin"));
+        assert!(!teams.allies("mroik", "mroik"));
+        assert!(!teams.allies("mroik", "carl"));
+        assert!(matches!(
+            teams.create("blue", "fin"),
+            Err(WoopError::AlreadyInTeam(_))
+        ));
+
+        teams.invite("fin", "carl").unwrap();
+        teams.leave("mroik").unwrap();
+        teams.leave("fin").unwrap();
+        assert_eq!(teams.iter().count(), 0);
+        // The team is gone and so are its invites
+        assert!(teams.accept("carl", "red").is_err());
+    }
+}
@@ -0,0 +1,224 @@
+use super::{error::WoopError, game::LAST_DAY};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Proposals a player can have waiting for an answer at the same time
+pub const MAX_PROPOSALS: usize = 10;
+
+/// Something a side of a treaty commits to
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case", tag = "term")]
+pub enum Term {
+    /// Points handed over as soon as the treaty is accepted
+    Points { amount: u16 },
+    /// Points held by the server from when the treaty is accepted, handed over at the start of
+    /// `day`
+    PointsOnDay { amount: u16, day: u8 },
+    /// Not shooting the zords of the other side until the end of `until`
+    NonAggression { until: u8 },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TreatyStatus {
+    /// Waiting for the counterpart to accept
+    Proposed,
+    /// Accepted, with terms still running
+    Active,
+    /// Every term was honored
+    Fulfilled,
+    Declined,
+    Withdrawn,
+    /// A side shot the other while it had promised not to
+    Broken {
+        by: String,
+    },
+}
+
+/// Points held by the server until `day`
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Escrow {
+    pub from: String,
+    pub to: String,
+    pub amount: u16,
+    pub day: u8,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Treaty {
+    pub id: u64,
+    pub proposer: String,
+    pub counterpart: String,
+    /// What the proposer commits to
+    pub gives: Vec<Term>,
+    /// What the counterpart commits to
+    pub asks: Vec<Term>,
+    pub status: TreatyStatus,
+    /// Points waiting to be handed over while the treaty is active
+    pub escrow: Vec<Escrow>,
+}
+
+/// Checks the terms of a side can still be honored on `day`
+pub fn validate(terms: &[Term], day: u8) -> Result<(), WoopError> {
+    for term in terms {
+        match term {
+            Term::Points { amount: 0 } | Term::PointsOnDay { amount: 0, .. } => {
+                return Err(WoopError::InvalidTreaty("Points must be more than 0"));
+            }
+            Term::PointsOnDay { day: due, .. } if *due <= day => {
+                return Err(WoopError::InvalidTreaty(
+                    "Points can only be promised for a later day",
+                ));
+            }
+            // Points promised for a later day have to be handed over before the game ends
+            Term::PointsOnDay { day: due, .. } if *due > LAST_DAY => {
+                return Err(WoopError::InvalidTreaty(
+                    "Points are promised after the game ends",
+                ));
+            }
+            Term::NonAggression { until } if *until < day => {
+                return Err(WoopError::InvalidTreaty(
+                    "Non-aggression can't end in the past",
+                ));
+            }
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+impl Treaty {
+    /// Each side with the other side and the terms it committed to
+    pub fn sides(&self) -> [(&str, &str, &[Term]); 2] {
+        [
+            (
+                self.proposer.as_str(),
+                self.counterpart.as_str(),
+                &self.gives,
+            ),
+            (
+                self.counterpart.as_str(),
+                self.proposer.as_str(),
+                &self.asks,
+            ),
+        ]
+    }
+
+    /// Points `player` puts down when the treaty is accepted
+    pub fn cost(&self, player: &str) -> u32 {
+        self.sides()
+            .into_iter()
+            .filter(|(party, _, _)| *party == player)
+            .flat_map(|(_, _, terms)| terms)
+            .map(|term| match term {
+                Term::Points { amount } | Term::PointsOnDay { amount, .. } => *amount as u32,
+                Term::NonAggression { .. } => 0,
+            })
+            .sum()
+    }
+
+    /// `player` promised not to shoot the zords of `target` on `day`
+    pub fn forbids_shot(&self, player: &str, target: &str, day: u8) -> bool {
+        self.status == TreatyStatus::Active
+            && self.sides().into_iter().any(|(party, other, terms)| {
+                party == player
+                    && other == target
+                    && terms
+                        .iter()
+                        .any(|term| matches!(term, Term::NonAggression { until } if *until >= day))
+            })
+    }
+
+    /// Nothing is left to hand over or to honor after `day`
+    pub fn done_after(&self, day: u8) -> bool {
+        self.escrow.is_empty()
+            && self.sides().into_iter().all(|(_, _, terms)| {
+                terms
+                    .iter()
+                    .all(|term| !matches!(term, Term::NonAggression { until } if *until > day))
+            })
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Treaties {
+    next_id: u64,
+    treaties: Vec<Treaty>,
+}
+
+impl Treaties {
+    pub fn propose(
+        &mut self,
+        proposer: &str,
+        counterpart: &str,
+        gives: Vec<Term>,
+        asks: Vec<Term>,
+    ) -> &Treaty {
+        self.treaties.push(Treaty {
+            id: self.next_id,
+            proposer: proposer.to_string(),
+            counterpart: counterpart.to_string(),
+            gives,
+            asks,
+            status: TreatyStatus::Proposed,
+            escrow: Vec::new(),
+        });
+        self.next_id += 1;
+        self.treaties.last().unwrap()
+    }
+
+    pub fn get_mut(&mut self, id: u64) -> Option<&mut Treaty> {
+        self.treaties.iter_mut().find(|treaty| treaty.id == id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Treaty> {
+        self.treaties.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Treaty> {
+        self.treaties.iter_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{validate, Escrow, Term, Treaties, TreatyStatus};
+
+    #[test]
+    fn treaty_terms() {
+        assert!(validate(&[Term::Points { amount: 0 }], 3).is_err());
+        assert!(validate(&[Term::PointsOnDay { amount: 5, day: 3 }], 3).is_err());
+        assert!(validate(&[Term::PointsOnDay { amount: 5, day: 29 }], 3).is_err());
+        assert!(validate(&[Term::NonAggression { until: 2 }], 3).is_err());
+        assert!(validate(&[Term::NonAggression { until: 3 }], 3).is_ok());
+
+        let mut treaties = Treaties::default();
+        treaties.propose(
+            "mroik",
+            "fin",
+            vec![
+                Term::Points { amount: 10 },
+                Term::NonAggression { until: 5 },
+            ],
+            vec![Term::PointsOnDay { amount: 10, day: 4 }],
+        );
+        let treaty = treaties.get_mut(0).unwrap();
+        assert_eq!(treaty.cost("mroik"), 10);
+        assert_eq!(treaty.cost("fin"), 10);
+        assert!(!treaty.forbids_shot("mroik", "fin", 3));
+
+        treaty.status = TreatyStatus::Active;
+        treaty.escrow.push(Escrow {
+            from: String::from("fin"),
+            to: String::from("mroik"),
+            amount: 10,
+            day: 4,
+        });
+        assert!(treaty.forbids_shot("mroik", "fin", 5));
+        assert!(!treaty.forbids_shot("mroik", "fin", 6));
+        assert!(!treaty.forbids_shot("fin", "mroik", 3));
+        treaty.escrow.clear();
+        assert!(!treaty.done_after(4));
+        assert!(treaty.done_after(5));
+    }
+}
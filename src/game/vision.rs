@@ -0,0 +1,93 @@
+use super::{game::Game, log::PlayerEvent, zord::distance};
+
+/// What a player can see of the board. Without a vision radius in the config the whole board is
+/// public, otherwise players only see the cells within the radius of their zords and of the ones
+/// of their allies. Totems are always visible.
+pub enum Sight {
+    Everything,
+    Around {
+        /// Nobody when the request isn't authenticated
+        player: Option<String>,
+        eyes: Vec<(i16, i16)>,
+        radius: i16,
+    },
+}
+
+impl Sight {
+    pub fn new(game: &Game, player: Option<&str>) -> Self {
+        let Some(radius) = game.vision else {
+            return Self::Everything;
+        };
+        let eyes = game
+            .zords
+            .iter()
+            .filter(|zord| {
+                player.is_some_and(|player| {
+                    zord.owner == player || game.teams.allies(player, zord.owner.as_str())
+                })
+            })
+            .map(|zord| (zord.x, zord.y))
+            .collect();
+        Self::Around {
+            player: player.map(String::from),
+            eyes,
+            radius: radius as i16,
+        }
+    }
+
+    pub fn sees(&self, (x, y): (i16, i16)) -> bool {
+        match self {
+            Self::Everything => true,
+            Self::Around { eyes, radius, .. } => {
+                eyes.iter().any(|eye| distance(*eye, (x, y)) <= *radius)
+            }
+        }
+    }
+
+    /// Players see the public events, the ones they took part in and the ones that happened in a
+    /// cell they can see now
+    pub fn sees_event(&self, event: &PlayerEvent) -> bool {
+        match self {
+            Self::Everything => true,
+            _ if event.public() => true,
+            Self::Around { player, .. } => {
+                player
+                    .as_deref()
+                    .is_some_and(|player| event.players().contains(&player))
+                    || event.coords().into_iter().any(|coord| self.sees(coord))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Sight;
+    use crate::{
+        config::Config,
+        game::{fixtures::move_zord, game::Game, zord::Zord},
+    };
+
+    #[test]
+    fn limited_sight() {
+        let config = Config {
+            vision: Some(3),
+            ..Config::with_players("test", &["mroik", "fin"])
+        };
+        let mut game = Game::new(&config);
+        game.zords.push(Zord::new("mroik", 0, 0));
+        game.zords.push(Zord::new("fin", 10, 10));
+
+        let sight = Sight::new(&game, Some("mroik"));
+        assert!(sight.sees((3, 3)));
+        assert!(!sight.sees((10, 10)));
+        assert!(sight.sees_event(&move_zord(0, "fin", (2, 1), (2, 1))));
+        assert!(!sight.sees_event(&move_zord(0, "fin", (10, 10), (10, 10))));
+        assert!(sight.sees_event(&move_zord(0, "mroik", (10, 10), (10, 10))));
+
+        let nobody = Sight::new(&game, None);
+        assert!(!nobody.sees((0, 0)));
+        game.vision = None;
+        assert!(Sight::new(&game, None).sees((10, 10)));
+    }
+}
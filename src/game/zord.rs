@@ -1,10 +1,10 @@
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
 pub const BASE_RANGE: u8 = 5;
 const BASE_HP: u8 = 2;
 
-#[derive(Debug, Serialize, ToSchema)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct Zord {
     pub x: i16,
     pub y: i16,
@@ -14,6 +14,11 @@ pub struct Zord {
     pub owner: String,
 }
 
+/// Squares between two cells, a diagonal step counts as one
+pub fn distance(a: (i16, i16), b: (i16, i16)) -> i16 {
+    (a.0 - b.0).abs().max((a.1 - b.1).abs())
+}
+
 // Since move requires for the board to be passed it is implemented in board
 impl Zord {
     pub fn new(owner: &str, x: i16, y: i16) -> Self {
@@ -0,0 +1,147 @@
+use serde::Serialize;
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Mutex,
+    },
+    time::UNIX_EPOCH,
+};
+use utoipa::ToSchema;
+
+#[derive(Serialize, Clone, ToSchema)]
+#[serde(rename_all = "snake_case", tag = "status")]
+pub enum Persistence {
+    /// Nothing is being persisted for this game
+    Disabled,
+    Ok {
+        last_success: u64,
+    },
+    Failing {
+        last_success: Option<u64>,
+        error: String,
+    },
+}
+
+/// Liveness information of the background work tied to a game, updated by the scheduler thread
+/// and by whatever persists the game state
+pub struct GameStatus {
+    scheduler_alive: AtomicBool,
+    last_tick: AtomicU64,
+    last_rollover: AtomicU64,
+    persistence: Mutex<Persistence>,
+}
+
+impl Default for GameStatus {
+    fn default() -> Self {
+        Self {
+            scheduler_alive: AtomicBool::new(false),
+            last_tick: AtomicU64::new(0),
+            last_rollover: AtomicU64::new(0),
+            persistence: Mutex::new(Persistence::Disabled),
+        }
+    }
+}
+
+impl GameStatus {
+    pub fn set_scheduler_alive(&self, alive: bool) {
+        self.scheduler_alive.store(alive, Ordering::SeqCst);
+    }
+
+    pub fn tick(&self) {
+        self.last_tick.store(unix_timestamp(), Ordering::SeqCst);
+    }
+
+    pub fn rolled_over(&self) {
+        self.last_rollover.store(unix_timestamp(), Ordering::SeqCst);
+    }
+
+    pub fn persisted<E: ToString>(&self, result: Result<(), E>) {
+        let mut persistence = self.persistence.lock().unwrap();
+        let last_success = match &*persistence {
+            Persistence::Disabled => None,
+            Persistence::Ok { last_success } => Some(*last_success),
+            Persistence::Failing { last_success, .. } => *last_success,
+        };
+        *persistence = match result {
+            Ok(()) => Persistence::Ok {
+                last_success: unix_timestamp(),
+            },
+            Err(err) => Persistence::Failing {
+                last_success,
+                error: err.to_string(),
+            },
+        };
+    }
+
+    pub fn scheduler_alive(&self) -> bool {
+        self.scheduler_alive.load(Ordering::SeqCst)
+    }
+
+    pub fn last_tick(&self) -> Option<u64> {
+        non_zero(self.last_tick.load(Ordering::SeqCst))
+    }
+
+    pub fn last_rollover(&self) -> Option<u64> {
+        non_zero(self.last_rollover.load(Ordering::SeqCst))
+    }
+
+    pub fn persistence(&self) -> Persistence {
+        self.persistence.lock().unwrap().clone()
+    }
+
+    /// The game is ready if its scheduler checked in recently and persistence isn't failing
+    pub fn is_ready(&self, max_tick_age: u64) -> bool {
+        let recent_tick = self
+            .last_tick()
+            .is_some_and(|tick| unix_timestamp().saturating_sub(tick) <= max_tick_age);
+        let persisting = !matches!(self.persistence(), Persistence::Failing { .. });
+        self.scheduler_alive() && recent_tick && persisting
+    }
+}
+
+fn non_zero(stamp: u64) -> Option<u64> {
+    match stamp {
+        0 => None,
+        _ => Some(stamp),
+    }
+}
+
+fn unix_timestamp() -> u64 {
+    UNIX_EPOCH.elapsed().unwrap().as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GameStatus, Persistence};
+
+    #[test]
+    fn ready_after_tick() {
+        let status = GameStatus::default();
+        assert!(!status.is_ready(60));
+        status.set_scheduler_alive(true);
+        status.tick();
+        assert!(status.is_ready(60));
+        status.set_scheduler_alive(false);
+        assert!(!status.is_ready(60));
+    }
+
+    #[test]
+    fn failing_persistence_keeps_last_success() {
+        let status = GameStatus::default();
+        status.set_scheduler_alive(true);
+        status.tick();
+        status.persisted::<String>(Ok(()));
+        status.persisted(Err("disk full"));
+        assert!(!status.is_ready(60));
+        match status.persistence() {
+            Persistence::Failing {
+                last_success,
+                error,
+            } => {
+                assert!(last_success.is_some());
+                assert_eq!(error, "disk full");
+            }
+            _ => panic!("Persistence should be failing"),
+        }
+    }
+}
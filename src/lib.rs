@@ -0,0 +1,9 @@
+pub mod api;
+pub mod config;
+pub mod export;
+pub mod game;
+pub mod health;
+pub mod metrics;
+pub mod registry;
+pub mod scheduler;
+pub mod snapshot;
@@ -1,50 +1,137 @@
-mod api;
-pub mod config;
-mod game;
-
-use api::api::start_api;
-use clap::Parser;
-use clokwerk::{Job, Scheduler, TimeUnits};
-use config::Config;
-use game::game::Game;
-use std::{
-    sync::{Arc, Mutex},
-    time::{Duration, SystemTime, UNIX_EPOCH},
+use clap::{Parser, Subcommand};
+use std::{path::PathBuf, process::exit, sync::Arc};
+use woop_attack::{
+    api::api::start_api,
+    config::Config,
+    export,
+    game::game::Game,
+    registry::GameRegistry,
+    scheduler::{persist, schedule_game},
+    snapshot,
 };
 
 #[derive(Parser)]
+#[command(args_conflicts_with_subcommands = true)]
 struct Args {
-    config: String,
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// One config file for each game hosted by the server
+    #[arg(required = true)]
+    configs: Vec<String>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Write the history of a game as CSV tables: events, daily scores and zord lifetimes
+    Export {
+        config: String,
+        /// Directory the tables are written to
+        dir: PathBuf,
+    },
+    /// Bundle the config, the final state and the full log of a game in a zip archive
+    Archive { config: String, file: PathBuf },
+}
+
+// Loads a game the way the server would, from its snapshot and its journal. Without a snapshot
+// there's no state to export, and starting a new game would write to the journal of the real one.
+fn load_game(config: &Config) -> Game {
+    let Some(path) = config.snapshot.as_deref() else {
+        eprintln!("Game {} has no snapshot to export from", config.id);
+        exit(1);
+    };
+    match snapshot::read(path, config).expect("Couldn't restore game") {
+        Some(game) => game,
+        None => {
+            eprintln!("Game {} has no snapshot at {}", config.id, path.display());
+            exit(1);
+        }
+    }
+}
+
+fn run_command(command: Command) {
+    let result = match &command {
+        Command::Export { config, dir } => {
+            let game = load_game(&Config::read_file(config.as_str()));
+            export::export_csv(&game, dir)
+        }
+        Command::Archive { config, file } => {
+            let game = load_game(&Config::read_file(config.as_str()));
+            export::write_archive(&game, config.as_ref(), file)
+        }
+    };
+    if let Err(err) = result {
+        eprintln!("Couldn't export the game: {}", err);
+        exit(1);
+    }
+}
+
+// Resolves on Ctrl-C or, on unix, on SIGTERM which is what docker sends when stopping a container
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Couldn't listen for SIGTERM")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {},
+        _ = terminate => {},
+    }
+    log::info!("Shutting down");
 }
 
 #[tokio::main]
 async fn main() {
     let args = Args::parse();
-    let config = Config::read_file(args.config.as_str());
+    if let Some(command) = args.command {
+        return run_command(command);
+    }
+    let configs: Vec<Config> = args
+        .configs
+        .iter()
+        .map(|path| Config::read_file(path.as_str()))
+        .collect();
 
     env_logger::init();
 
-    let game = Arc::new(Mutex::new(Game::new(&config)));
-    let scheduler_game = game.clone();
-
-    let mut scheduler = Scheduler::new();
-    scheduler.every(1.day()).at("6:00 am").run(move || {
-        let mut game = scheduler_game.lock().unwrap();
-        let start_stamp = game.start_of_day.duration_since(UNIX_EPOCH).unwrap();
-        let current_stamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
-        let passed_day = start_stamp <= current_stamp;
-        if (game.day > 0 && game.day < 29) || (game.day == 0 && passed_day) {
-            game.new_day();
-        }
-    });
-    let scheduler_handler = scheduler.watch_thread(Duration::from_secs(60));
+    let mut registry = GameRegistry::new();
+    let mut scheduler_handlers = Vec::new();
+    for config in configs {
+        let game = match config.snapshot.as_deref() {
+            Some(path) => snapshot::read(path, &config).expect("Couldn't restore game"),
+            None => None,
+        };
+        let game = game.unwrap_or_else(|| Game::new(&config));
+        let entry = registry.insert(config, game);
+        scheduler_handlers.push(schedule_game(
+            entry.game.clone(),
+            &entry.config,
+            entry.status.clone(),
+        ));
 
-    game.lock()
-        .unwrap()
-        .auth
-        .iter()
-        .for_each(|(user, pass)| println!("User: {}\nToken: {}", user, pass));
+        println!("Game: {}", entry.config.id);
+        let game = entry.game.read().await;
+        game.auth
+            .iter()
+            .for_each(|(user, pass)| println!("User: {}\nToken: {}", user, pass));
+        game.spectators
+            .iter()
+            .for_each(|(user, pass)| println!("Spectator: {}\nToken: {}", user, pass));
+    }
+
+    let registry = Arc::new(registry);
+    start_api(registry.clone(), shutdown_signal()).await;
 
-    start_api(game).await;
-    scheduler_handler.stop();
+    // No request is being handled at this point, stopping the schedulers as well makes sure
+    // nothing changes the games after the final snapshot
+    scheduler_handlers
+        .into_iter()
+        .for_each(|handler| handler.stop());
+    for (_, entry) in registry.iter() {
+        persist(&*entry.game.read().await, &entry.config, &entry.status);
+    }
 }
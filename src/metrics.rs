@@ -0,0 +1,289 @@
+use crate::{game::error::WoopError, registry::GameRegistry};
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::{LazyLock, Mutex},
+    time::{Duration, SystemTime},
+};
+
+/// Metrics collected by the api handlers, the game state is read on every scrape instead
+pub static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::default);
+
+const LATENCY_BUCKETS: [f64; 10] = [0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5];
+
+// Routes of a game, anything else is reported as a static file or as "other" to keep the amount
+// of labels bounded
+const GAME_ROUTES: &[&str] = &[
+    "shoot",
+    "move",
+    "shield",
+    "increase-range",
+    "donate-points",
+    "build-zord",
+    "batch",
+    "preview",
+    "orders",
+    "map",
+    "leaderboard",
+    "me",
+    "teams",
+    "treaties",
+    "messages",
+    "stream",
+    "stats",
+    "graph",
+    "day",
+    "activity",
+    "spectate",
+    "auth",
+];
+const SERVER_ROUTES: &[&str] = &["games", "docs", "rapidoc", "metrics", "healthz", "readyz"];
+
+#[derive(Default)]
+struct Histogram {
+    buckets: [u64; LATENCY_BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, value: f64) {
+        LATENCY_BUCKETS
+            .iter()
+            .zip(self.buckets.iter_mut())
+            .filter(|(bound, _)| value <= **bound)
+            .for_each(|(_, bucket)| *bucket += 1);
+        self.sum += value;
+        self.count += 1;
+    }
+}
+
+#[derive(Default)]
+pub struct Metrics {
+    requests: Mutex<BTreeMap<(String, u16), u64>>,
+    latencies: Mutex<BTreeMap<String, Histogram>>,
+    errors: Mutex<BTreeMap<(String, &'static str), u64>>,
+}
+
+impl Metrics {
+    pub fn observe_request(&self, path: &str, status: u16, elapsed: Duration) {
+        let route = route_label(path);
+        *self
+            .requests
+            .lock()
+            .unwrap()
+            .entry((route.clone(), status))
+            .or_insert(0) += 1;
+        self.latencies
+            .lock()
+            .unwrap()
+            .entry(route)
+            .or_default()
+            .observe(elapsed.as_secs_f64());
+    }
+
+    pub fn observe_error(&self, game: &str, err: &WoopError) {
+        *self
+            .errors
+            .lock()
+            .unwrap()
+            .entry((game.to_string(), err.kind()))
+            .or_insert(0) += 1;
+    }
+
+    /// Renders every metric in the Prometheus text format
+    pub async fn render(&self, registry: &GameRegistry) -> String {
+        let mut out = String::new();
+
+        out.push_str("# HELP woop_http_requests_total Requests handled by route and status\n");
+        out.push_str("# TYPE woop_http_requests_total counter\n");
+        for ((route, status), count) in self.requests.lock().unwrap().iter() {
+            writeln!(
+                out,
+                "woop_http_requests_total{{route=\"{}\",status=\"{}\"}} {}",
+                route, status, count
+            )
+            .unwrap();
+        }
+
+        out.push_str("# HELP woop_http_request_duration_seconds Latency of requests by route\n");
+        out.push_str("# TYPE woop_http_request_duration_seconds histogram\n");
+        for (route, hist) in self.latencies.lock().unwrap().iter() {
+            for (bound, count) in LATENCY_BUCKETS.iter().zip(hist.buckets.iter()) {
+                writeln!(
+                    out,
+                    "woop_http_request_duration_seconds_bucket{{route=\"{}\",le=\"{}\"}} {}",
+                    route, bound, count
+                )
+                .unwrap();
+            }
+            writeln!(
+                out,
+                "woop_http_request_duration_seconds_bucket{{route=\"{}\",le=\"+Inf\"}} {}",
+                route, hist.count
+            )
+            .unwrap();
+            writeln!(
+                out,
+                "woop_http_request_duration_seconds_sum{{route=\"{}\"}} {}",
+                route, hist.sum
+            )
+            .unwrap();
+            writeln!(
+                out,
+                "woop_http_request_duration_seconds_count{{route=\"{}\"}} {}",
+                route, hist.count
+            )
+            .unwrap();
+        }
+
+        out.push_str("# HELP woop_action_errors_total Rejected actions by game and error\n");
+        out.push_str("# TYPE woop_action_errors_total counter\n");
+        for ((game, kind), count) in self.errors.lock().unwrap().iter() {
+            writeln!(
+                out,
+                "woop_action_errors_total{{game=\"{}\",error=\"{}\"}} {}",
+                escape(game),
+                kind,
+                count
+            )
+            .unwrap();
+        }
+
+        let mut events = String::new();
+        let mut zords = String::new();
+        let mut points = String::new();
+        let mut actions = String::new();
+        let mut days = String::new();
+        let mut rollover = String::new();
+        for (id, entry) in registry.iter() {
+            let id = escape(id);
+            let game = entry.game.read().await;
+            for (kind, count) in game.logged_actions.counts() {
+                writeln!(
+                    events,
+                    "woop_events_total{{game=\"{}\",kind=\"{}\"}} {}",
+                    id, kind, count
+                )
+                .unwrap();
+            }
+            writeln!(
+                zords,
+                "woop_active_zords{{game=\"{}\"}} {}",
+                id,
+                game.zords.len()
+            )
+            .unwrap();
+            for player in game.players.values() {
+                let name = escape(player.name.as_str());
+                writeln!(
+                    points,
+                    "woop_player_points{{game=\"{}\",player=\"{}\"}} {}",
+                    id, name, player.points
+                )
+                .unwrap();
+                writeln!(
+                    actions,
+                    "woop_player_actions{{game=\"{}\",player=\"{}\"}} {}",
+                    id, name, player.actions
+                )
+                .unwrap();
+            }
+            writeln!(days, "woop_game_day{{game=\"{}\"}} {}", id, game.day).unwrap();
+            let until = game
+                .next_rollover()
+                .duration_since(SystemTime::now())
+                .unwrap_or_default();
+            writeln!(
+                rollover,
+                "woop_seconds_until_rollover{{game=\"{}\"}} {}",
+                id,
+                until.as_secs()
+            )
+            .unwrap();
+        }
+
+        for (name, kind, help, body) in [
+            (
+                "woop_events_total",
+                "counter",
+                "Logged events by kind",
+                events,
+            ),
+            ("woop_active_zords", "gauge", "Zords on the board", zords),
+            (
+                "woop_player_points",
+                "gauge",
+                "Points of each player",
+                points,
+            ),
+            (
+                "woop_player_actions",
+                "gauge",
+                "Actions left to each player",
+                actions,
+            ),
+            ("woop_game_day", "gauge", "Current day of the game", days),
+            (
+                "woop_seconds_until_rollover",
+                "gauge",
+                "Time left before the next day starts",
+                rollover,
+            ),
+        ] {
+            writeln!(out, "# HELP {} {}", name, help).unwrap();
+            writeln!(out, "# TYPE {} {}", name, kind).unwrap();
+            out.push_str(body.as_str());
+        }
+        out
+    }
+}
+
+fn route_label(path: &str) -> String {
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    match segments.as_slice() {
+        ["games", _, route, ..] if GAME_ROUTES.contains(route) => {
+            format!("/games/{{id}}/{}", route)
+        }
+        [route] if SERVER_ROUTES.contains(route) => format!("/{}", route),
+        [""] | ["index.html"] | ["style.css"] | ["client.js"] => String::from("static"),
+        _ => String::from("other"),
+    }
+}
+
+fn escape(label: &str) -> String {
+    label
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{escape, route_label, Histogram};
+
+    #[test]
+    fn route_labels() {
+        assert_eq!(route_label("/games/woop/shoot"), "/games/{id}/shoot");
+        assert_eq!(route_label("/games"), "/games");
+        assert_eq!(route_label("/"), "static");
+        assert_eq!(route_label("/games/woop/../etc/passwd"), "other");
+    }
+
+    #[test]
+    fn escape_labels() {
+        assert_eq!(escape("woop"), "woop");
+        assert_eq!(escape("a\"b\\c\nd"), "a\\\"b\\\\c\\nd");
+    }
+
+    #[test]
+    fn histogram_buckets() {
+        let mut hist = Histogram::default();
+        hist.observe(0.02);
+        hist.observe(3.0);
+        assert_eq!(hist.count, 2);
+        assert_eq!(hist.buckets[2], 0);
+        assert_eq!(hist.buckets[3], 1);
+        assert_eq!(hist.buckets[9], 1);
+    }
+}
@@ -0,0 +1,53 @@
+use crate::{
+    config::Config,
+    game::{error::WoopError, game::Game},
+    health::GameStatus,
+};
+use std::{collections::BTreeMap, sync::Arc};
+use tokio::sync::RwLock;
+
+/// A game shared between the api handlers and the scheduler. Handlers that only read the game
+/// state take the read lock, so they never wait on each other.
+pub type SharedGame = Arc<RwLock<Game>>;
+
+pub struct GameEntry {
+    pub config: Config,
+    pub game: SharedGame,
+    pub status: Arc<GameStatus>,
+}
+
+/// All the games hosted by this server, keyed by game id
+#[derive(Default)]
+pub struct GameRegistry {
+    games: BTreeMap<String, GameEntry>,
+}
+
+impl GameRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, config: Config, game: Game) -> &GameEntry {
+        let id = config.id.clone();
+        if self.games.contains_key(id.as_str()) {
+            panic!("Game {} is defined more than once", id);
+        }
+        let entry = GameEntry {
+            config,
+            game: Arc::new(RwLock::new(game)),
+            status: Arc::new(GameStatus::default()),
+        };
+        self.games.entry(id).or_insert(entry)
+    }
+
+    pub fn get(&self, id: &str) -> Result<SharedGame, WoopError> {
+        match self.games.get(id) {
+            Some(entry) => Ok(entry.game.clone()),
+            None => Err(WoopError::GameNotFound(id.to_string())),
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &GameEntry)> {
+        self.games.iter()
+    }
+}
@@ -0,0 +1,140 @@
+use crate::{
+    config::Config,
+    game::{
+        bot::play_turn,
+        game::{Game, Phase, LAST_DAY},
+    },
+    health::GameStatus,
+    registry::SharedGame,
+    snapshot,
+};
+use clokwerk::{Job, Scheduler, TimeUnits};
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// How often the scheduler thread wakes up to check for pending jobs
+pub const TICK_INTERVAL: Duration = Duration::from_secs(60);
+
+// Clears the liveness flag even when the thread unwinds because of a panic
+struct AliveGuard(Arc<GameStatus>);
+
+impl Drop for AliveGuard {
+    fn drop(&mut self) {
+        self.0.set_scheduler_alive(false);
+    }
+}
+
+pub struct SchedulerHandle {
+    stop: Arc<AtomicBool>,
+    thread: JoinHandle<()>,
+}
+
+impl SchedulerHandle {
+    /// Halt the scheduler thread and wait for it to finish the job it's running
+    pub fn stop(self) {
+        self.stop.store(true, Ordering::SeqCst);
+        self.thread.thread().unpark();
+        if self.thread.join().is_err() {
+            log::error!("Scheduler thread panicked");
+        }
+    }
+}
+
+/// Saves the game and flushes its activity log if the config asks for it, reporting the outcome
+/// in `status`
+pub fn persist(game: &Game, config: &Config, status: &GameStatus) {
+    if config.snapshot.is_none() && config.log_dir.is_none() {
+        return;
+    }
+    let mut result = game.logged_actions.sync();
+    if let (Ok(()), Some(path)) = (&result, config.snapshot.as_deref()) {
+        result = snapshot::write(game, path);
+    }
+    if let Err(err) = &result {
+        log::error!("Couldn't persist game {}: {}", game.id, err);
+    }
+    status.persisted(result);
+}
+
+fn run_orders(game: &SharedGame) {
+    // Most ticks have nothing to play, which doesn't need to hold up the players
+    let due = {
+        let game = game.blocking_read();
+        game.phase() == Phase::Running && game.orders_due()
+    };
+    if !due {
+        return;
+    }
+    let mut game = game.blocking_write();
+    if game.phase() == Phase::Running {
+        let played = game.run_orders();
+        if played > 0 {
+            log::info!("Played {} orders in {}", played, game.id);
+        }
+    }
+}
+
+/// Advances the game to the next day every day at the rollover time of the config and plays the
+/// turns of the bots at their times. At every tick the orders that are due are played and the
+/// game is saved.
+pub fn schedule_game(
+    game: SharedGame,
+    config: &Config,
+    status: Arc<GameStatus>,
+) -> SchedulerHandle {
+    let mut scheduler = Scheduler::new();
+    let job_status = status.clone();
+    let job_game = game.clone();
+    scheduler
+        .every(1.day())
+        .at_time(config.rollover)
+        .run(move || {
+            let mut game = job_game.blocking_write();
+            let start_stamp = game.start_of_day.duration_since(UNIX_EPOCH).unwrap();
+            let current_stamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+            let passed_day = start_stamp <= current_stamp;
+            if (game.day > 0 && game.day <= LAST_DAY) || (game.day == 0 && passed_day) {
+                game.new_day();
+                job_status.rolled_over();
+            }
+        });
+
+    for bot in config.bots.iter() {
+        for time in bot.play_at.iter() {
+            let game = game.clone();
+            let bot = bot.clone();
+            scheduler.every(1.day()).at_time(*time).run(move || {
+                let mut game = game.blocking_write();
+                if game.phase() == Phase::Running {
+                    let played =
+                        play_turn(&mut game, bot.name.as_str(), bot.strategy.bot().as_mut());
+                    log::info!("Bot {} played {} actions in {}", bot.name, played, game.id);
+                }
+            });
+        }
+    }
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let thread = thread::spawn({
+        let stop = stop.clone();
+        let config = config.clone();
+        move || {
+            status.set_scheduler_alive(true);
+            let _guard = AliveGuard(status.clone());
+            while !stop.load(Ordering::SeqCst) {
+                scheduler.run_pending();
+                run_orders(&game);
+                persist(&game.blocking_read(), &config, &status);
+                status.tick();
+                thread::park_timeout(TICK_INTERVAL);
+            }
+        }
+    });
+    SchedulerHandle { stop, thread }
+}
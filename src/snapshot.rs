@@ -0,0 +1,80 @@
+use crate::{config::Config, game::game::Game};
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, ErrorKind, Write},
+    path::Path,
+};
+
+/// Writes the whole game state to `path`. The snapshot is written to a temporary file first, so
+/// a crash halfway through never leaves a truncated snapshot behind.
+pub fn write(game: &Game, path: &Path) -> io::Result<()> {
+    let tmp = path.with_extension("tmp");
+    let file = File::create(&tmp)?;
+    let mut writer = BufWriter::new(file);
+    serde_json::to_writer(&mut writer, game)?;
+    writer.flush()?;
+    writer.get_ref().sync_all()?;
+    fs::rename(tmp, path)
+}
+
+/// Restores the game saved at `path`, if there is one
+pub fn read(path: &Path, config: &Config) -> io::Result<Option<Game>> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err),
+    };
+    let mut game: Game = serde_json::from_reader(BufReader::new(file))?;
+    game.id = config.id.clone();
+    game.rollover = config.rollover;
+    game.vision = config.vision;
+    game.spectator_delay = config.spectator_delay;
+    game.team_rules = config.team_rules;
+    game.reveal_messages = config.reveal_messages;
+    game.set_spectators(&config.spectators);
+    if let Some(dir) = config.log_dir.as_deref() {
+        game.logged_actions.attach_journal(dir, config.log_fsync)?;
+    }
+    Ok(Some(game))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read, write};
+    use crate::{config::Config, game::game::Game};
+    use std::{env, fs};
+
+    #[test]
+    fn restore_snapshot() {
+        let path = env::temp_dir().join(format!("woop-snapshot-{}.json", std::process::id()));
+        let mut config = Config {
+            snapshot: Some(path.clone()),
+            spectators: vec![String::from("caster"), String::from("judge")],
+            ..Config::with_players("test", &["mroik", "fin"])
+        };
+        let mut game = Game::new(&config);
+        game.new_day();
+        write(&game, &path).unwrap();
+
+        let restored = read(&path, &config).unwrap().unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(restored.id, "test");
+        assert_eq!(restored.day, 1);
+        assert_eq!(restored.auth, game.auth);
+        assert_eq!(restored.zords.len(), 2);
+        assert_eq!(
+            restored.logged_actions.counts(),
+            game.logged_actions.counts()
+        );
+
+        // Spectators dropped from the config lose their token, the others keep it
+        write(&game, &path).unwrap();
+        config.spectators = vec![String::from("caster"), String::from("host")];
+        let restored = read(&path, &config).unwrap().unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(restored.spectators.len(), 2);
+        assert_eq!(restored.spectators["caster"], game.spectators["caster"]);
+        assert!(restored.spectators.contains_key("host"));
+        assert!(!restored.spectators.contains_key("judge"));
+    }
+}